@@ -1,73 +1,657 @@
-use crate::{dirs, config::Config};
-use std::path::PathBuf;
+use crate::{
+    atomic, config::Config, dirs, error::*, format::Format, memory::InMemoryConfig, registry,
+};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug)]
+/// Name of the default config file.
+const DEFAULT_CONFIG_FILE: &str = "config.ini";
+/// Name of the default config file before 0.3.
+const LEGACY_CONFIG_FILE: &str = "config";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct App {
     name: &'static str,
     config_file: Option<&'static str>,
+    windows_roaming: bool,
+    version: Option<&'static str>,
 }
 
 impl App {
-    /// Handle to the default config file.
+    /// Records your application's version string, e.g. `env!("CARGO_PKG_VERSION")`.
+    pub const fn with_version(self, version: &'static str) -> App {
+        App {
+            version: Some(version),
+            ..self
+        }
+    }
+
+    /// Your application's name, as given to `kettle::app`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Adds your application to the process-wide registry, see `kettle::registered_apps`.
+    /// Registering an equal app more than once has no effect.
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// APP.register();
+    /// assert_eq!(kettle::find_app("app"), Some(&APP));
+    /// ```
+    pub fn register(&self) {
+        registry::register(*self)
+    }
+
+    /// Your application's version string, if one was set with `with_version`.
+    pub fn version(&self) -> Option<&'static str> {
+        self.version
+    }
+
+    /// Chooses whether `data_dir` uses the roaming (`%APPDATA%`, the default) or the local
+    /// (`%LOCALAPPDATA%`) profile on Windows. Has no effect on other platforms.
+    pub const fn windows_roaming(self, roaming: bool) -> App {
+        App {
+            windows_roaming: roaming,
+            ..self
+        }
+    }
+
+    /// Handle to the default config file, `config.ini` unless another name was given to
+    /// `kettle::app`.
+    ///
+    /// Versions before 0.3 named the default file `config`. If only that file exists, it is
+    /// renamed to `config.ini`; should the rename fail, the handle points to `config` instead.
     pub fn config(&self) -> Config {
         let dir = dirs::config_dir().join(self.name);
-        let file = self.config_file.unwrap_or("config");
+        let file = match self.config_file {
+            Some(file) => file,
+            None => default_config_file(&dir),
+        };
         Config::from(dir, file)
     }
 
-    /// Handle to a named config file.
+    /// Handle to `section` of the default config file, short for `config().section(section)`.
+    pub fn preferences(&self, section: &'static str) -> Config {
+        self.config().section(section)
+    }
+
+    /// Handle to a named config file, read and written in the `Format` matching its extension.
     pub fn config_file(&self, file: &'static str) -> Config {
         let dir = dirs::config_dir().join(self.name);
         Config::from(dir, file)
     }
 
+    /// Handle to a named config file that is always read and written as `ini`, whatever its
+    /// extension.
+    pub fn ini_file(&self, file: &'static str) -> Config {
+        self.config_file(file).with_format(Format::Ini)
+    }
+
+    /// Handle to an in-memory config that is never written to disk.
+    ///
+    /// All handles for the same app name share their values until the process exits.
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// APP.runtime_config().set("session", Some("abc")).unwrap();
+    /// assert_eq!(APP.runtime_config().get("session"), Some("abc".to_string()));
+    /// ```
+    pub fn runtime_config(&self) -> InMemoryConfig {
+        InMemoryConfig::shared(self.name)
+    }
+
+    /// A stable identifier for this installation of your application.
+    ///
+    /// Read from the `_installation_id` key of the default config; on first use a random
+    /// 32-character hex string is generated and stored there.
+    pub fn installation_id(&self) -> Result<String> {
+        self.config()
+            .get_or_generate("_installation_id", random_hex)
+    }
+
+    /// Whether this is the first launch of your application.
+    ///
+    /// Checks the `_initialized` key of the default config; if it is absent, it is set to `true`
+    /// and this returns `true`, so later calls return `false`.
+    pub fn first_run(&self) -> Result<bool> {
+        let config = self.config();
+        if config.try_get("_initialized")?.is_some() {
+            return Ok(false);
+        }
+        config.set("_initialized", Some("true"))?;
+        Ok(true)
+    }
+
+    /// When `update_last_run_time` was last called, read from the `_last_run_time` key of the
+    /// default config as an RFC 3339 timestamp.
+    #[cfg(feature = "chrono")]
+    pub fn last_run_time(&self) -> Result<Option<SystemTime>> {
+        let value = match self.config().try_get("_last_run_time")? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match chrono::DateTime::parse_from_rfc3339(&value) {
+            Ok(time) => Ok(Some(time.into())),
+            Err(_) => Err(KettleError::ParseError {
+                key: "_last_run_time".to_string(),
+                value,
+                type_name: "RFC 3339 timestamp",
+            }),
+        }
+    }
+
+    /// Records the current time as the last run time of your application.
+    #[cfg(feature = "chrono")]
+    pub fn update_last_run_time(&self) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        self.config().set("_last_run_time", Some(now))
+    }
+
+    /// Paths of all config files in your application's config directory, sorted alphabetically.
+    ///
+    /// Only files with an extension of a supported `Format` (`.ini`, plus `.toml`/`.json`
+    /// when enabled) are listed. Returns an empty list if the directory does not exist.
+    pub fn list_config_files(&self) -> Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(self.config_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let known = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(Format::from_extension)
+                .is_some();
+            if known && path.is_file() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// PathBuf for the user's home directory (not app-specific)
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let home_dir = APP.home_dir();
+    /// ```
+    pub fn home_dir(&self) -> PathBuf {
+        dirs::home_dir()
+    }
+
+    /// The `xdg` crate's resolver for your application's XDG base directories, for lookups
+    /// beyond what `kettle` provides (e.g. `find_config_files` or `place_config_file`).
+    #[cfg(all(unix, feature = "xdg"))]
+    pub fn xdg_dirs(&self) -> Result<xdg::BaseDirectories> {
+        Ok(xdg::BaseDirectories::with_prefix(self.name)?)
+    }
+
     /// PathBuf for your application's cache directory
     /// ## Example
     /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
     /// let cache_dir = APP.cache_dir();
     /// ```
     pub fn cache_dir(&self) -> PathBuf {
-        dirs::cache_dir().join(&self.name)
+        dirs::cache_dir().join(self.name)
+    }
+
+    /// PathBuf for downloaded update packages, inside your application's cache directory
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let update_dir = APP.update_dir(); // $HOME/.cache/app/updates
+    /// ```
+    pub fn update_dir(&self) -> PathBuf {
+        self.cache_dir().join("updates")
+    }
+
+    /// Removes the update directory and any packages in it.
+    pub fn cleanup_update_dir(&self) -> Result<()> {
+        match fs::remove_dir_all(self.update_dir()) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// PathBuf for an embedded database, such as SQLite, in your application's cache directory
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let thumbnails = APP.cache_db("thumbnails"); // $HOME/.cache/app/thumbnails.db
+    /// ```
+    pub fn cache_db(&self, name: &str) -> PathBuf {
+        self.cache_dir().join(format!("{}.db", name))
     }
 
     /// PathBuf for your application's config directory
     /// ## Example
     /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
     /// let config_dir = APP.config_dir();
     /// ```
     pub fn config_dir(&self) -> PathBuf {
-        dirs::config_dir().join(&self.name)
+        dirs::config_dir().join(self.name)
     }
 
     /// PathBuf for your application's data directory
     /// ## Example
     /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
     /// let data_dir = APP.data_dir();
     /// ```
     pub fn data_dir(&self) -> PathBuf {
-        dirs::data_dir().join(&self.name)
+        if cfg!(windows) && !self.windows_roaming {
+            dirs::data_local_dir().join(self.name)
+        } else {
+            dirs::data_dir().join(self.name)
+        }
+    }
+
+    /// PathBuf for an embedded database, such as SQLite, in your application's data directory
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let library = APP.data_db("library"); // $HOME/.local/share/app/library.db
+    /// ```
+    pub fn data_db(&self, name: &str) -> PathBuf {
+        self.data_dir().join(format!("{}.db", name))
+    }
+
+    /// PathBuf for a versioned subdirectory of your application's data directory
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let data_v2 = APP.data_dir_versioned(2); // $DATA_DIR/app/v2
+    /// assert!(data_v2.ends_with("app/v2"));
+    /// ```
+    pub fn data_dir_versioned(&self, version: u32) -> PathBuf {
+        self.data_dir().join(format!("v{}", version))
+    }
+
+    /// PathBuf for a version-suffixed data file, `<name>.v<version>`
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let library = APP.data_file_versioned("library.db", 3); // $DATA_DIR/app/library.db.v3
+    /// assert!(library.ends_with("app/library.db.v3"));
+    /// ```
+    pub fn data_file_versioned(&self, name: &str, version: u32) -> PathBuf {
+        self.data_dir().join(format!("{}.v{}", name, version))
+    }
+
+    /// Highest version of the data file `name` present in your application's data directory,
+    /// see `data_file_versioned`.
+    pub fn latest_data_file_version(&self, name: &str) -> Result<Option<u32>> {
+        let entries = match fs::read_dir(self.data_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let prefix = format!("{}.v", name);
+        let mut latest = None;
+        for entry in entries {
+            let file_name = entry?.file_name();
+            let version = file_name
+                .to_str()
+                .and_then(|f| f.strip_prefix(&prefix))
+                .and_then(|v| v.parse::<u32>().ok());
+            latest = latest.max(version);
+        }
+        Ok(latest)
+    }
+
+    /// Reads the `data_version` key from the default config file.
+    ///
+    /// Returns `0` if no version has been recorded yet.
+    pub fn current_data_version(&self) -> Result<u32> {
+        match self.config().try_get("data_version")? {
+            Some(value) => value.parse().map_err(|_| KettleError::ParseError {
+                key: "data_version".to_string(),
+                value,
+                type_name: "u32",
+            }),
+            None => Ok(0),
+        }
     }
 
     /// PathBuf for your application's local data directory
     /// ## Example
     /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
     /// let data_local_dir = APP.data_local_dir();
     /// ```
     pub fn data_local_dir(&self) -> PathBuf {
-        dirs::data_local_dir().join(&self.name)
+        dirs::data_local_dir().join(self.name)
+    }
+
+    /// Adds `path` to the front of your application's recently opened files, dropping earlier
+    /// occurrences of it and keeping at most `max_entries` entries.
+    ///
+    /// The list is stored as a newline-separated file at `$DATA_DIR/<app>/recent_files`, so
+    /// paths that are not valid UTF-8 or contain line breaks fail with
+    /// `KettleError::ValidationError`.
+    pub fn add_to_recent_files(&self, path: &Path, max_entries: usize) -> Result<()> {
+        match path.to_str() {
+            Some(line) if !line.contains(['\n', '\r']) => {}
+            _ => {
+                return Err(KettleError::ValidationError(format!(
+                    "{} cannot be stored in the recent files list",
+                    path.display()
+                )))
+            }
+        }
+        let mut files = self.recent_files()?;
+        files.retain(|file| file != path);
+        files.insert(0, path.to_path_buf());
+        files.truncate(max_entries);
+        let contents: String = files
+            .iter()
+            .map(|file| format!("{}\n", file.to_string_lossy()))
+            .collect();
+        dirs::create_dir_all(&self.data_dir())?;
+        atomic::write(&self.data_dir().join("recent_files"), contents)?;
+        Ok(())
+    }
+
+    /// Your application's recently opened files, most recent first.
+    pub fn recent_files(&self) -> Result<Vec<PathBuf>> {
+        match fs::read_to_string(self.data_dir().join("recent_files")) {
+            Ok(contents) => Ok(contents
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// PathBuf for your application's state directory, for data such as logs and history
+    /// that should persist but is not worth backing up
+    ///
+    /// Falls back to the local data directory on platforms without a state directory.
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let state_dir = APP.state_dir(); // $HOME/.local/state/app
+    /// ```
+    pub fn state_dir(&self) -> PathBuf {
+        dirs::state_dir()
+            .map(|d| d.join(self.name))
+            .unwrap_or_else(|| self.data_local_dir())
+    }
+
+    /// PathBuf for your application's log directory, inside its state directory
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let log_dir = APP.log_dir(); // $HOME/.local/state/app/logs
+    /// ```
+    pub fn log_dir(&self) -> PathBuf {
+        self.state_dir().join("logs")
+    }
+
+    /// PathBuf for a log file in your application's log directory, with `.log` appended if
+    /// `name` has no extension. The file is not created.
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let log_file = APP.log_file("server"); // $HOME/.local/state/app/logs/server.log
+    /// assert!(log_file.ends_with("logs/server.log"));
+    /// ```
+    pub fn log_file(&self, name: &str) -> PathBuf {
+        let file = self.log_dir().join(name);
+        if file.extension().is_some() {
+            file
+        } else {
+            file.with_extension("log")
+        }
+    }
+
+    /// Creates your application's config, data, cache and state directories if they do not
+    /// exist yet, stopping at the first that cannot be created, and returns their paths.
+    pub fn create_default_dirs(&self) -> Result<Vec<PathBuf>> {
+        let paths = vec![
+            self.config_dir(),
+            self.data_dir(),
+            self.cache_dir(),
+            self.state_dir(),
+        ];
+        for dir in &paths {
+            dirs::create_dir_all(dir)?;
+        }
+        Ok(paths)
     }
 
     /// PathBuf for your application's preference directory
     /// ## Example
     /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
     /// let preference_dir = APP.preference_dir();
     /// ```
     pub fn preference_dir(&self) -> PathBuf {
-        dirs::preference_dir().join(&self.name)
+        dirs::preference_dir().join(self.name)
     }
 
+    /// First existing config file named `name`, searching your application's user config
+    /// directory and then its system-wide config directories, per the XDG lookup order.
+    pub fn find_config_file(&self, name: &str) -> Option<PathBuf> {
+        Some(self.config_dir().join(name))
+            .filter(|path| path.is_file())
+            .or_else(|| self.find_system_config_file(name))
+    }
+
+    /// PathBufs for your application's system-wide config directories from `$XDG_CONFIG_DIRS`
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let system_config_dirs = APP.system_config_dirs(); // [/etc/xdg/app]
+    /// ```
+    pub fn system_config_dirs(&self) -> Vec<PathBuf> {
+        dirs::system_config_dirs()
+            .into_iter()
+            .map(|d| d.join(self.name))
+            .collect()
+    }
+
+    /// First existing file named `name` in your application's system-wide config directories.
+    pub fn find_system_config_file(&self, name: &str) -> Option<PathBuf> {
+        self.system_config_dirs()
+            .into_iter()
+            .map(|d| d.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// PathBuf for your application's `size` pixel square icon, where the platform has one
+    ///
+    /// Follows the `hicolor` icon theme layout on Linux and the BSDs. macOS has no per-user
+    /// icon directory, so icons go in the data directory; Windows embeds icons in executables
+    /// and returns `None`. The file is not created.
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let icon = APP.icon_path(48); // $HOME/.local/share/icons/hicolor/48x48/apps/app.png
+    /// ```
+    pub fn icon_path(&self, size: u32) -> Option<PathBuf> {
+        let size_dir = format!("{0}x{0}", size);
+        if cfg!(windows) {
+            None
+        } else if cfg!(target_os = "macos") {
+            Some(self.data_dir().join("icons").join(size_dir + ".png"))
+        } else {
+            Some(
+                dirs::data_dir()
+                    .join("icons/hicolor")
+                    .join(size_dir)
+                    .join("apps")
+                    .join(format!("{}.png", self.name)),
+            )
+        }
+    }
+
+    /// Writes `$XDG_DATA_HOME/desktop-directories/<app>.directory`, which XDG application menus
+    /// use to show a category for your application with `display_name` and `icon`.
+    ///
+    /// Does nothing on Windows and macOS.
+    pub fn create_xdg_menu_entry(&self, display_name: &str, icon: &str) -> Result<()> {
+        fn escape(value: &str) -> String {
+            value
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+                .replace('\t', "\\t")
+        }
+        if cfg!(any(windows, target_os = "macos")) {
+            return Ok(());
+        }
+        let dir = dirs::data_dir().join("desktop-directories");
+        dirs::create_dir_all(&dir)?;
+        let entry = format!(
+            "[Desktop Entry]\nType=Directory\nName={}\nIcon={}\n",
+            escape(display_name),
+            escape(icon)
+        );
+        atomic::write(&dir.join(format!("{}.directory", self.name)), entry)?;
+        Ok(())
+    }
+
+    /// PathBuf for your application's font directory, where the platform has one
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let font_dir = APP.font_dir(); // $HOME/.local/share/fonts/app
+    /// ```
+    pub fn font_dir(&self) -> Option<PathBuf> {
+        dirs::font_dir().map(|d| d.join(self.name))
+    }
+
+    /// PathBuf for your application's template directory, where the platform has one
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let template_dir = APP.template_dir();
+    /// ```
+    pub fn template_dir(&self) -> Option<PathBuf> {
+        dirs::template_dir().map(|d| d.join(self.name))
+    }
+
+    /// Your application's executable filename on this platform (`<name>.exe` on Windows)
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let executable = APP.executable_name();
+    /// ```
+    pub fn executable_name(&self) -> String {
+        format!("{}{}", self.name, std::env::consts::EXE_SUFFIX)
+    }
+
+    /// PathBuf for your application's runtime directory, where the platform has one
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let runtime_dir = APP.runtime_dir(); // $XDG_RUNTIME_DIR/app
+    /// ```
+    pub fn runtime_dir(&self) -> Option<PathBuf> {
+        dirs::runtime_dir().map(|d| d.join(self.name))
+    }
+
+    /// Creates your application's runtime directory, if the platform has one, with
+    /// user-only (`0700`) permissions on Unix as the XDG spec requires.
+    pub fn ensure_runtime_dir(&self) -> Result<Option<PathBuf>> {
+        let dir = match self.runtime_dir() {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        dirs::create_dir_all(&dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(Some(dir))
+    }
+
+    /// Creates and returns a directory for data that only lives as long as this process,
+    /// `<runtime_dir>/<pid>`, or `<temp_dir>/<app>/<pid>` where there is no runtime directory.
+    ///
+    /// Remove it with `cleanup_session_dir` before exiting.
+    pub fn session_dir(&self) -> Result<PathBuf> {
+        let dir = match self.ensure_runtime_dir()? {
+            Some(dir) => dir,
+            None => std::env::temp_dir().join(self.name),
+        };
+        let session = dir.join(std::process::id().to_string());
+        dirs::create_dir_all(&session)?;
+        Ok(session)
+    }
+
+    /// Removes the directory created by `session_dir`, along with everything in it.
+    pub fn cleanup_session_dir(&self) -> Result<()> {
+        let dir = self
+            .runtime_dir()
+            .unwrap_or_else(|| std::env::temp_dir().join(self.name));
+        match fs::remove_dir_all(dir.join(std::process::id().to_string())) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// PathBuf for a Unix domain socket named `name` in your application's runtime directory
+    ///
+    /// Falls back to the temp directory where no runtime directory exists (e.g. macOS) and
+    /// returns `None` on Windows. The directory is not created.
+    /// ## Example
+    /// ```
+    /// # const APP: kettle::App = kettle::app("app", None);
+    /// let socket = APP.socket_path("daemon"); // $XDG_RUNTIME_DIR/app/daemon.sock
+    /// ```
+    pub fn socket_path(&self, name: &str) -> Option<PathBuf> {
+        if cfg!(windows) {
+            return None;
+        }
+        let dir = dirs::runtime_dir().unwrap_or_else(std::env::temp_dir);
+        Some(dir.join(self.name).join(format!("{}.sock", name)))
+    }
+}
+
+/// Name of the default config file in `dir`, moving a legacy `config` file to `config.ini`.
+///
+/// The legacy name is only returned if the rename failed and left the legacy file in place;
+/// a rename that failed because another caller moved the file first yields `config.ini`.
+fn default_config_file(dir: &Path) -> &'static str {
+    let (current, legacy) = (dir.join(DEFAULT_CONFIG_FILE), dir.join(LEGACY_CONFIG_FILE));
+    let needs_migration = || !current.exists() && legacy.is_file();
+    if needs_migration() && fs::rename(&legacy, &current).is_err() && needs_migration() {
+        return LEGACY_CONFIG_FILE;
+    }
+    DEFAULT_CONFIG_FILE
 }
 
+/// 128 random bits as hex, seeded from the randomly keyed hasher in `std`.
+fn random_hex() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    (0..2u8)
+        .map(|i| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u8(i);
+            hasher.write_u128(nanos);
+            hasher.write_u32(std::process::id());
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
 
 /// Initializes a `kettle::App`.
 ///
@@ -75,12 +659,94 @@ impl App {
 /// across your code.
 /// # Example
 /// ```
-/// pub(crate) const THIS_APP: kettle::App = kettle::app("this_APP", None)
+/// pub(crate) const THIS_APP: kettle::App = kettle::app("this_APP", None);
 ///
 /// // you can optionally define a custom default config filename
-/// pub(crate) const THIS_APP: kettle::App = kettle::app("this_APP", Some("config.ini")
+/// pub(crate) const OTHER_APP: kettle::App = kettle::app("other_APP", Some("config.ini"));
+///
+/// // on Windows, `data_dir` can point to the local rather than the roaming profile
+/// pub(crate) const LOCAL_APP: kettle::App = kettle::app("local_APP", None).windows_roaming(false);
+///
+/// // the app version can be recorded for migrations
+/// pub(crate) const VERSIONED_APP: kettle::App =
+///     kettle::app("versioned_APP", None).with_version(env!("CARGO_PKG_VERSION"));
 /// ```
 pub const fn app(name: &'static str, config_file: Option<&'static str>) -> App {
-    App { name, config_file }
+    App {
+        name,
+        config_file,
+        windows_roaming: true,
+        version: None,
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_config_file_is_renamed() {
+        let dir = std::env::temp_dir().join(format!("kettle-app-legacy-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(default_config_file(&dir), "config.ini");
+        assert!(!dir.join("config.ini").exists());
+
+        fs::write(dir.join("config"), "view = horizontal\n").unwrap();
+        assert_eq!(default_config_file(&dir), "config.ini");
+        assert!(!dir.join("config").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("config.ini")).unwrap(),
+            "view = horizontal\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_migration_always_yields_config_ini() {
+        let dir =
+            std::env::temp_dir().join(format!("kettle-app-legacy-race-{}", std::process::id()));
+        for _ in 0..1000 {
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("config"), "view = horizontal\n").unwrap();
+            let barrier = std::sync::Barrier::new(16);
+            std::thread::scope(|scope| {
+                for _ in 0..16 {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        assert_eq!(default_config_file(&dir), "config.ini");
+                    });
+                }
+            });
+            assert!(!dir.join("config").exists());
+            assert_eq!(
+                fs::read_to_string(dir.join("config.ini")).unwrap(),
+                "view = horizontal\n"
+            );
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recent_files_rejects_unstorable_paths() {
+        const APP: App = app("kettle-recent-files-test", None);
+        for path in ["two\nlines", "carriage\rreturn"] {
+            assert!(matches!(
+                APP.add_to_recent_files(Path::new(path), 10),
+                Err(KettleError::ValidationError(_))
+            ));
+        }
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+            let path = Path::new(OsStr::from_bytes(b"not\xffutf8"));
+            assert!(matches!(
+                APP.add_to_recent_files(path, 10),
+                Err(KettleError::ValidationError(_))
+            ));
+        }
+        assert!(!APP.data_dir().exists());
+    }
+}
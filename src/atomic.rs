@@ -0,0 +1,160 @@
+//! Atomic file writes.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Most symlinks followed when resolving the target of a write, as in Linux's `MAXSYMLINKS`.
+const MAX_SYMLINKS: usize = 40;
+
+/// Number of temporary files created by this process, to keep their names unique.
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes `contents` to a temporary file next to `path` and renames it into place, so readers
+/// never observe a partially written file.
+///
+/// If `path` is a symlink, the file it points to is replaced and the link is kept. The
+/// permissions of an existing file carry over to the new one.
+pub(crate) fn write<C: AsRef<[u8]>>(path: &Path, contents: C) -> io::Result<()> {
+    let path = resolve_symlinks(path)?;
+    let (tmp, mut file) = create_tmp(&path)?;
+    let result = (|| {
+        file.write_all(contents.as_ref())?;
+        match fs::metadata(&path) {
+            Ok(metadata) => file.set_permissions(metadata.permissions())?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        drop(file);
+        fs::rename(&tmp, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Follows `path` through any symlinks to the file they point to, which need not exist.
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_SYMLINKS {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = fs::read_link(&path)?;
+                path = match path.parent() {
+                    Some(parent) => parent.join(target),
+                    None => target,
+                };
+            }
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(path),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::other("too many levels of symbolic links"))
+}
+
+/// Creates a new temporary file next to `path`, named after the process, thread and a
+/// per-process counter so concurrent writers never share one.
+fn create_tmp(path: &Path) -> io::Result<(PathBuf, fs::File)> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut hasher = DefaultHasher::new();
+    thread::current().id().hash(&mut hasher);
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.{:x}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id(),
+        hasher.finish(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)?;
+    Ok((tmp, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kettle-atomic-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replaces_contents() {
+        let dir = scratch_dir("contents");
+        let path = dir.join("config.ini");
+        write(&path, "a = 1\n").unwrap();
+        write(&path, "a = 2\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_symlinks() {
+        let dir = scratch_dir("symlink");
+        let target = dir.join("real.ini");
+        let link = dir.join("config.ini");
+        fs::write(&target, "a = 1\n").unwrap();
+        std::os::unix::fs::symlink("real.ini", &link).unwrap();
+        write(&link, "a = 2\n").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "a = 2\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = scratch_dir("permissions");
+        let path = dir.join("config.ini");
+        fs::write(&path, "a = 1\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        write(&path, "a = 2\n").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_writes_succeed() {
+        let dir = scratch_dir("concurrent");
+        let path = Arc::new(dir.join("config.ini"));
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let path = Arc::clone(&path);
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        write(&path, format!("thread = {}\n", i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let contents = fs::read_to_string(&*path).unwrap();
+        assert!(contents.starts_with("thread = ") && contents.lines().count() == 1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+//! Keeping `ini` comments across saves.
+//!
+//! The `ini` writer drops comments, so before a save the existing file is scanned for comment
+//! and blank lines. Each run of them is attached to the section header or key that follows it
+//! and re-inserted in front of that entry when the new contents are written. The file's line
+//! endings are kept as well.
+
+use std::collections::HashMap;
+use std::mem;
+
+/// A section header (key `None`) or a key. Sections that appear more than once are told apart
+/// by how many sections of the same name precede them.
+type Entry = (Option<String>, usize, Option<String>);
+
+#[derive(Default)]
+pub(crate) struct Comments {
+    /// Lines preceding a section header or a key.
+    before: HashMap<Entry, Vec<String>>,
+    /// Lines after the last entry of the file.
+    trailing: Vec<String>,
+    /// Line ending of the original file, if it has any lines.
+    newline: Option<&'static str>,
+}
+
+fn is_comment(line: &str) -> bool {
+    line.is_empty() || line.starts_with(';') || line.starts_with('#')
+}
+
+fn header(line: &str) -> Option<&str> {
+    line.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+fn key(line: &str) -> Option<&str> {
+    line.find(['=', ':']).map(|i| line[..i].trim())
+}
+
+/// Tracks which section the lines of a file belong to.
+#[derive(Default)]
+struct Position {
+    section: Option<String>,
+    occurrence: usize,
+    seen: HashMap<String, usize>,
+}
+
+impl Position {
+    /// The entry on `line`, a trimmed non-comment line, or `None` if it is neither a section
+    /// header nor a key.
+    fn entry(&mut self, line: &str) -> Option<Entry> {
+        if let Some(name) = header(line) {
+            let seen = self.seen.entry(name.to_string()).or_insert(0);
+            self.section = Some(name.to_string());
+            self.occurrence = *seen;
+            *seen += 1;
+            Some((self.section.clone(), self.occurrence, None))
+        } else {
+            let key = key(line)?;
+            Some((self.section.clone(), self.occurrence, Some(key.to_string())))
+        }
+    }
+}
+
+impl Comments {
+    pub(crate) fn parse(text: &str) -> Self {
+        let mut comments = Comments {
+            newline: text.find('\n').map(|i| {
+                if text[..i].ends_with('\r') {
+                    "\r\n"
+                } else {
+                    "\n"
+                }
+            }),
+            ..Comments::default()
+        };
+        let mut pending = Vec::new();
+        let mut position = Position::default();
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if is_comment(trimmed) {
+                pending.push(line.to_string());
+                continue;
+            }
+            let entry = match position.entry(trimmed) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if !pending.is_empty() {
+                let lines = mem::take(&mut pending);
+                comments.before.entry(entry).or_insert(lines);
+            }
+        }
+        comments.trailing = pending;
+        comments
+    }
+
+    /// Replaces the comment lines in front of `key`, keeping blank lines that precede them.
+    /// Each line of `comment` is written as a `;` comment.
+    ///
+    /// Like the `ini` writer's `set_to`, a section that appears more than once is looked up by
+    /// its first occurrence.
+    pub(crate) fn set(&mut self, section: Option<&str>, key: &str, comment: &str) {
+        let entry = (section.map(String::from), 0, Some(key.to_string()));
+        let lines = self.before.entry(entry).or_default();
+        let blank = lines
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        lines.truncate(blank);
+        lines.extend(comment.lines().map(|line| match line {
+            "" => ";".to_string(),
+            line => format!("; {}", line),
+        }));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.trailing.is_empty()
+    }
+
+    /// Re-inserts the comments into `written`, the output of the `ini` writer.
+    pub(crate) fn apply(&self, written: &str) -> String {
+        if self.is_empty() && self.newline.is_none() {
+            return written.to_string();
+        }
+        let newline = self.newline.unwrap_or(if written.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        });
+        let mut out: Vec<&str> = Vec::new();
+        let mut position = Position::default();
+        for line in written.lines() {
+            let trimmed = line.trim();
+            // the writer separates sections with blank lines, which the comments now provide
+            if trimmed.is_empty() {
+                continue;
+            }
+            if let Some(entry) = position.entry(trimmed) {
+                match self.before.get(&entry) {
+                    // blank lines that separated an entry from the one before it are not
+                    // needed once it has moved to the top of the file
+                    Some(lines) if out.is_empty() => out.extend(
+                        lines
+                            .iter()
+                            .map(String::as_str)
+                            .skip_while(|line| line.trim().is_empty()),
+                    ),
+                    Some(lines) => out.extend(lines.iter().map(String::as_str)),
+                    None if entry.2.is_none() && !out.is_empty() => out.push(""),
+                    None => {}
+                }
+            }
+            out.push(line);
+        }
+        out.extend(self.trailing.iter().map(String::as_str));
+
+        let mut text = out.join(newline);
+        text.push_str(newline);
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::write_ini;
+    use ini::{ini::Properties, Ini};
+
+    use super::Comments;
+
+    /// Parses `original`, runs `edit` over it and writes it back with its comments.
+    fn rewrite(original: &str, edit: impl FnOnce(&mut Ini)) -> String {
+        let mut config = Ini::load_from_str(original).unwrap();
+        edit(&mut config);
+        write_ini(&config, &Comments::parse(original)).unwrap()
+    }
+
+    #[test]
+    fn keeps_header_comments() {
+        let original = "; global\nname=kettle\n\n; appearance\n[view]\nmode=dark\n";
+        let written = rewrite(original, |c| {
+            c.set_to(Some("view"), "mode".to_string(), "light".to_string())
+        });
+        assert_eq!(
+            written,
+            "; global\nname=kettle\n\n; appearance\n[view]\nmode=light\n"
+        );
+    }
+
+    #[test]
+    fn keeps_key_comments() {
+        let original = "[view]\n# width in pixels\nwidth=80\n\n; height in rows\nheight=24\n";
+        let written = rewrite(original, |c| {
+            c.set_to(Some("view"), "height".to_string(), "40".to_string())
+        });
+        assert_eq!(
+            written,
+            "[view]\n# width in pixels\nwidth=80\n\n; height in rows\nheight=40\n"
+        );
+    }
+
+    #[test]
+    fn keeps_trailing_lines() {
+        let original = "a=1\n\n; end of file\n";
+        let written = rewrite(original, |c| {
+            c.set_to(None::<&str>, "b".to_string(), "2".to_string())
+        });
+        assert_eq!(written, "a=1\nb=2\n\n; end of file\n");
+    }
+
+    #[test]
+    fn drops_comments_of_deleted_keys() {
+        let original = "; first\na=1\n; second\nb=2\n";
+        let written = rewrite(original, |c| {
+            c.delete_from(None::<&str>, "a");
+        });
+        assert_eq!(written, "; second\nb=2\n");
+    }
+
+    #[test]
+    fn comments_follow_reordered_entries() {
+        let original = "[b]\n; about y\ny=2\n; about x\nx=1\n; section a\n[a]\nk=v\n";
+        let parsed = Ini::load_from_str(original).unwrap();
+        let mut reordered = Ini::new();
+        for section in ["a", "b"] {
+            let properties = parsed.section(Some(section)).unwrap();
+            let mut keys: Vec<_> = properties.iter().collect();
+            keys.sort();
+            let entry = reordered
+                .entry(Some(section.to_string()))
+                .or_insert_with(Properties::new);
+            for (key, value) in keys {
+                entry.append(key, value);
+            }
+        }
+        let written = write_ini(&reordered, &Comments::parse(original)).unwrap();
+        assert_eq!(
+            written,
+            "; section a\n[a]\nk=v\n\n[b]\n; about x\nx=1\n; about y\ny=2\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments_of_duplicate_sections_apart() {
+        let original = "; first\n[a]\nk=1\n\n; second\n[a]\n; about m\nm=3\n";
+        let written = rewrite(original, |c| {
+            c.set_to(Some("a"), "k".to_string(), "2".to_string())
+        });
+        assert_eq!(
+            written,
+            "; first\n[a]\nk=2\n\n; second\n[a]\n; about m\nm=3\n"
+        );
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let original = "; note\r\n[a]\r\nk=1\r\n";
+        let written = rewrite(original, |c| {
+            c.set_to(Some("a"), "k".to_string(), "2".to_string())
+        });
+        assert_eq!(written, "; note\r\n[a]\r\nk=2\r\n");
+    }
+
+    #[test]
+    fn set_replaces_comment_and_keeps_blank_lines() {
+        let original = "a=1\n\n; old\nb=2\n";
+        let mut comments = Comments::parse(original);
+        comments.set(None, "b", "new\n\nlines");
+        let config = Ini::load_from_str(original).unwrap();
+        let written = write_ini(&config, &comments).unwrap();
+        assert_eq!(written, "a=1\n\n; new\n;\n; lines\nb=2\n");
+    }
+}
@@ -1,41 +1,665 @@
-use crate::error::*;
-use ini::Ini;
+use crate::{
+    atomic,
+    comments::Comments,
+    dirs,
+    error::*,
+    format::{self, Format},
+    merged::{env_key, MergedConfig},
+    values,
+};
+use ini::{ini::Properties, Ini};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
+/// How often `Config::watch_and_reload` checks the config file for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Order in which sections and keys are written to the config file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum WriteOrder {
+    /// Sections and keys are kept in the order they were first added.
+    #[default]
+    Insertion,
+    /// Sections and the keys within them are sorted alphabetically.
+    Alphabetical,
+    /// Listed sections are written first, in the given order, followed by any others.
+    Manual(Vec<&'static str>),
+}
+
+/// Handle to a config file.
+///
+/// Every `get` reads the file and every `set` rewrites it. Comments and blank lines in `ini`
+/// files are kept in front of the section or key they precede; other formatting, such as
+/// spacing around `=` or quoting, is normalized on save.
+///
+/// `Config` is `Send + Sync` and can be shared between threads. Each save goes to its own
+/// temporary file, which is then renamed over the config file, so readers see either the old
+/// or the new contents, never a mix, even while other threads save. Concurrent `set`s are not
+/// serialized, though: two threads writing at once may each overwrite the other's change.
 pub struct Config {
-    path: (PathBuf, &'static str),
-    section: Option<&'static str>,
+    path: PathBuf,
+    section: Option<Cow<'static, str>>,
+    order: WriteOrder,
+    format: Format,
+    sanitize: bool,
+    #[cfg(feature = "cache")]
+    cache: RwLock<Option<(SystemTime, Ini)>>,
 }
 impl Config {
     pub fn from(dir: PathBuf, file: &'static str) -> Self {
+        Self::from_file(dir.join(file))
+    }
+
+    /// Handle to a config file at an arbitrary path, not scoped to any `App`.
+    pub fn from_file(path: PathBuf) -> Self {
         Self {
-            path: (dir, file),
+            format: Format::from_path(&path),
+            path,
             section: None,
+            order: WriteOrder::default(),
+            sanitize: false,
+            #[cfg(feature = "cache")]
+            cache: RwLock::new(None),
         }
     }
 
-    // Gets value from config. Will return `None` if key or config file does not exist.
+    /// File format used to read and write this config, inferred from the file extension.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Gets value from config. Will return `None` if key or config file does not exist.
     pub fn get(&self, key: &str) -> Option<String> {
-        if let Ok(config) = self.load() {
-            if let Some(value) = config.get_from(self.section, key) {
-                Some(value.to_string())
-            } else {
-                None
+        self.try_get(key).unwrap_or(None)
+    }
+    /// Gets value from config with leading and trailing whitespace removed.
+    ///
+    /// The `ini` parser already trims unquoted values, but whitespace inside quotes
+    /// (`key = " value "`) and in other formats is kept by `get`.
+    pub fn get_trimmed(&self, key: &str) -> Option<String> {
+        self.get(key).map(|v| v.trim().to_string())
+    }
+    /// Gets value from config, treating empty and whitespace-only values as not set.
+    pub fn get_non_empty(&self, key: &str) -> Option<String> {
+        self.get(key).filter(|v| !v.trim().is_empty())
+    }
+    /// Gets value from config, or `default` if the key or config file does not exist.
+    pub fn get_or(&self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+    /// Gets a boolean from config, or `default` if the key is missing or not one of
+    /// `true`/`false`, `1`/`0` or `yes`/`no` (in any case).
+    pub fn get_bool_or(&self, key: &str, default: bool) -> bool {
+        self.get(key)
+            .and_then(|v| values::parse_bool(&v))
+            .unwrap_or(default)
+    }
+    /// Gets an integer from config, or `default` if the key is missing or not an integer.
+    pub fn get_int_or(&self, key: &str, default: i64) -> i64 {
+        self.get(key)
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(default)
+    }
+    /// Gets a float from config, or `default` if the key is missing or not a number.
+    pub fn get_float_or(&self, key: &str, default: f64) -> f64 {
+        self.get(key)
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(default)
+    }
+    /// Gets value from the `env_var` environment variable if set, otherwise from config.
+    pub fn get_or_env(&self, key: &str, env_var: &str) -> Option<String> {
+        std::env::var(env_var).ok().or_else(|| self.get(key))
+    }
+    /// Gets value from config like `get`, but keeps the parsed file in memory and only reads
+    /// it again once its modification time is newer than the cached copy. The first call on
+    /// each handle always reads from disk.
+    #[cfg(feature = "cache")]
+    pub fn get_cached(&self, key: &str) -> Option<String> {
+        let modified = fs::metadata(self.path()).and_then(|m| m.modified()).ok()?;
+        let cache = self.cache.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some((cached, config)) = cache.as_ref() {
+            if modified <= *cached {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(file = %self.path().display(), key, "config get from cache");
+                return config
+                    .get_from(self.section_name(), key)
+                    .map(|v| self.value(v));
             }
+        }
+        drop(cache);
+        let config = self.load().ok()?;
+        let value = config
+            .get_from(self.section_name(), key)
+            .map(|v| self.value(v));
+        *self.cache.write().unwrap_or_else(PoisonError::into_inner) = Some((modified, config));
+        value
+    }
+    /// Gets value from config, surfacing read and parse errors.
+    ///
+    /// A missing config file is treated the same as a missing key and returns `Ok(None)`.
+    pub fn try_get(&self, key: &str) -> Result<Option<String>> {
+        let value = self
+            .load_or_default()?
+            .get_from(self.section_name(), key)
+            .map(|v| self.value(v));
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            file = %self.path().display(),
+            key,
+            section = ?self.section,
+            value_len = ?value.as_ref().map(String::len),
+            "config get"
+        );
+        Ok(value)
+    }
+    /// Gets an integer value from config, checking that it lies within `min..=max`.
+    pub fn get_int_in_range(&self, key: &str, min: i64, max: i64) -> Result<Option<i64>> {
+        let int: i64 = match self.try_get_parsed(key, "i64")? {
+            Some(int) => int,
+            None => return Ok(None),
+        };
+        if int < min || int > max {
+            return Err(KettleError::ValidationError(format!(
+                "value `{}` for key `{}` is outside the allowed range {}..={}",
+                int, key, min, max
+            )));
+        }
+        Ok(Some(int))
+    }
+    /// Gets a socket address such as `127.0.0.1:8080` or `[::1]:8080` from config, failing if
+    /// the stored value is not a valid address.
+    pub fn get_socket_addr(&self, key: &str) -> Result<Option<SocketAddr>> {
+        self.try_get_parsed(key, "socket address")
+    }
+    /// Gets any `FromStr` value, such as an enum variant, from config, failing with
+    /// `KettleError::ParseError` if the stored value does not parse.
+    pub fn get_enum<T: FromStr>(&self, key: &str) -> Result<Option<T>> {
+        self.try_get_parsed(key, std::any::type_name::<T>())
+    }
+    fn try_get_parsed<T: FromStr>(&self, key: &str, type_name: &'static str) -> Result<Option<T>> {
+        match self.try_get(key)? {
+            Some(value) => match value.parse() {
+                Ok(parsed) => Ok(Some(parsed)),
+                Err(_) => Err(KettleError::ParseError {
+                    key: key.to_string(),
+                    value,
+                    type_name,
+                }),
+            },
+            None => Ok(None),
+        }
+    }
+    /// Gets a duration such as `30s`, `5m`, `2h` or `1d` from config. Will return `None` if the
+    /// key does not exist or the value cannot be parsed.
+    pub fn get_duration(&self, key: &str) -> Option<Duration> {
+        values::parse_duration(&self.get(key)?)
+    }
+    /// Gets a value from config, falling back to the file of the same name in the system-wide
+    /// config directories, e.g. `/etc/xdg/<app>/config.ini`. `<app>` is the name of this
+    /// config's directory, which for configs from `App` is the app name.
+    pub fn get_from_default_or_local(&self, key: &str) -> Option<String> {
+        self.get(key).or_else(|| {
+            let app = self.dir().file_name()?;
+            let file = self.path().file_name()?;
+            dirs::system_config_dirs()
+                .into_iter()
+                .map(|d| d.join(app).join(file))
+                .filter(|path| path.is_file())
+                .find_map(|path| {
+                    let mut system = Config::from_file(path);
+                    system.section = self.section.clone();
+                    system.format = self.format;
+                    system.sanitize = self.sanitize;
+                    system.get(key)
+                })
+        })
+    }
+    /// Gets an ISO 8601 date (`YYYY-MM-DD`) from config. Will return `None` if the value is
+    /// not a valid date.
+    #[cfg(feature = "chrono")]
+    pub fn get_date(&self, key: &str) -> Option<chrono::NaiveDate> {
+        chrono::NaiveDate::parse_from_str(&self.get(key)?, "%Y-%m-%d").ok()
+    }
+    /// Gets a path from config, expanding a leading `~` to the home directory and resolving
+    /// relative paths against the config file's directory. The path does not need to exist.
+    pub fn get_absolute_path(&self, key: &str) -> Option<PathBuf> {
+        let value = self.get(key)?;
+        let path = if value == "~" {
+            dirs::home_dir()
+        } else if let Some(rest) = value.strip_prefix("~/") {
+            dirs::home_dir().join(rest)
+        } else {
+            PathBuf::from(value)
+        };
+        if path.is_absolute() {
+            Some(path)
         } else {
-            None
+            Some(self.dir().join(path))
         }
     }
-    // Sets value to config. Keys with `None` values are removed.
+    /// Gets an array stored with `set_array`, collecting `key[0]`, `key[1]`, ... up to the first
+    /// missing index.
+    pub fn get_array(&self, key: &str) -> Vec<String> {
+        let config = match self.load() {
+            Ok(config) => config,
+            Err(_) => return Vec::new(),
+        };
+        (0..)
+            .map(|i| config.get_from(self.section_name(), &format!("{}[{}]", key, i)))
+            .take_while(Option::is_some)
+            .flatten()
+            .map(|v| self.value(v))
+            .collect()
+    }
+    /// Gets a `#RRGGBB` hex color from config. Will return `None` if the key does not exist or
+    /// the value is not a hex color; named colors are not supported.
+    pub fn get_color(&self, key: &str) -> Option<(u8, u8, u8)> {
+        values::parse_color(&self.get(key)?)
+    }
+    /// Gets a log level (`trace`, `debug`, `info`, `warn` or `error`, in any case) from config.
+    #[cfg(feature = "log")]
+    pub fn get_log_level(&self, key: &str) -> Option<log::Level> {
+        self.get(key)?.trim().parse().ok()
+    }
+    /// Gets a URL from config, failing if the stored value is not a valid URL.
+    #[cfg(feature = "url")]
+    pub fn get_url(&self, key: &str) -> Result<Option<url::Url>> {
+        match self.try_get(key)? {
+            Some(value) => Ok(Some(url::Url::parse(&value)?)),
+            None => Ok(None),
+        }
+    }
+    /// Gets a UUID from config. Will return `None` if the key does not exist or the value is
+    /// not a valid UUID.
+    #[cfg(feature = "uuid")]
+    pub fn get_uuid(&self, key: &str) -> Option<uuid::Uuid> {
+        uuid::Uuid::parse_str(self.get(key)?.trim()).ok()
+    }
+    /// Gets a UUID from config, generating and storing a random (v4) one if the key does not
+    /// hold a valid UUID yet.
+    #[cfg(feature = "uuid")]
+    pub fn ensure_uuid(&self, key: &'static str) -> Result<uuid::Uuid> {
+        if let Some(uuid) = self.get_uuid(key) {
+            return Ok(uuid);
+        }
+        let uuid = uuid::Uuid::new_v4();
+        self.set_uuid(key, uuid)?;
+        Ok(uuid)
+    }
+    /// Gets value from config, or generates one with `generator`, sets it to config and
+    /// returns it if the key does not exist yet. The generated value is saved atomically, in
+    /// the same load-save cycle as the lookup.
+    pub fn get_or_generate<F: FnOnce() -> String>(
+        &self,
+        key: &'static str,
+        generator: F,
+    ) -> Result<String> {
+        let mut config = self.load_or_create()?;
+        if let Some(value) = config.get_from(self.section_name(), key) {
+            return Ok(self.value(value));
+        }
+        let value = generator();
+        config.set_to(self.section_name(), key.to_string(), value.clone());
+        self.save(config)?;
+        Ok(value)
+    }
+    /// Sets the value made by `factory` on first access to `key`, the same as
+    /// `get_or_generate`. `factory` is not called if the key is already set.
+    pub fn get_or_set_generated<F: FnOnce() -> String>(
+        &self,
+        key: &'static str,
+        factory: F,
+    ) -> Result<String> {
+        self.get_or_generate(key, factory)
+    }
+    /// Gets the value of the first of `keys` present in config, checked in order.
+    pub fn get_with_fallback(&self, keys: &[&str]) -> Option<String> {
+        let config = self.load().ok()?;
+        keys.iter()
+            .find_map(|key| config.get_from(self.section_name(), key))
+            .map(|v| self.value(v))
+    }
+    /// Gets the value of `key` from each of `sections`, as `(section, value)` pairs.
+    pub fn get_from_sections(&self, key: &str, sections: &[&str]) -> Vec<(String, Option<String>)> {
+        let config = self.load().ok();
+        sections
+            .iter()
+            .map(|section| {
+                let value = config
+                    .as_ref()
+                    .and_then(|c| c.get_from(Some(*section), key))
+                    .map(|v| self.value(v));
+                (section.to_string(), value)
+            })
+            .collect()
+    }
+    /// Number of keys in the selected section, or `0` if the section or file does not exist.
+    pub fn count_keys(&self) -> Result<usize> {
+        Ok(self
+            .load_or_default()?
+            .section(self.section_name())
+            .map_or(0, |properties| properties.len()))
+    }
+    /// All entries of the selected section, or of the global section if none is selected.
+    /// Empty if the section or file does not exist.
+    pub fn section_to_map(&self) -> HashMap<String, String> {
+        self.into_iter().collect()
+    }
+    /// Keys of the selected section whose values parse as `T`, with the parsed values. Values
+    /// that do not parse are skipped.
+    pub fn keys_typed<T: FromStr>(&self) -> Result<Vec<(String, T)>> {
+        let config = self.load_or_default()?;
+        Ok(config
+            .section(self.section_name())
+            .into_iter()
+            .flat_map(|properties| properties.iter())
+            .filter_map(|(key, value)| Some((key.to_string(), self.value(value).parse().ok()?)))
+            .collect())
+    }
+    /// Names of all sections in the file, excluding the global section.
+    pub fn section_names(&self) -> Vec<String> {
+        match self.load() {
+            Ok(config) => config.sections().flatten().map(String::from).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+    /// Number of named sections in the file, not counting the global section.
+    pub fn count_sections(&self) -> Result<usize> {
+        Ok(self
+            .load_or_default()?
+            .sections()
+            .filter(Option::is_some)
+            .count())
+    }
+    /// Gets value from a dotted `section.key` path, ignoring the section set on this handle.
+    ///
+    /// The path is split on the first `.`; paths without one are looked up in the global section.
+    pub fn get_dotted(&self, path: &str) -> Option<String> {
+        let (section, key) = match path.split_once('.') {
+            Some((section, key)) => (Some(section), key),
+            None => (None, path),
+        };
+        let config = self.load().ok()?;
+        config.get_from(section, key).map(|v| self.value(v))
+    }
+    /// Sets value to config. Keys with `None` values are removed.
+    ///
+    /// The file is created if it does not exist. An existing file that cannot be parsed is
+    /// left untouched and the parse error is returned.
     pub fn set<S: Into<String>>(&self, key: &'static str, value: Option<S>) -> Result<()> {
-        if let Ok(config) = self.load() {
-            self.save(self.set_or_delete(config, key, value)?)
-        } else {
-            self.create_empty()?;
-            self.save(self.set_or_delete(self.load()?, key, value)?)
+        let value: Option<String> = value.map(Into::into);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            file = %self.path().display(),
+            key,
+            section = ?self.section,
+            value_len = ?value.as_ref().map(String::len),
+            "config set"
+        );
+        let config = self.load_or_create()?;
+        self.save(self.set_or_delete(config, key, value)?)
+    }
+    /// Sets value to config like `set` and writes `comment` on the lines before the key,
+    /// replacing any comment already there, in a single load-save cycle.
+    ///
+    /// Only `ini` files keep comments; other formats fail with `KettleError::ValidationError`.
+    pub fn set_with_comment<S: Into<String>>(
+        &self,
+        key: &'static str,
+        value: Option<S>,
+        comment: &str,
+    ) -> Result<()> {
+        if self.format != Format::Ini {
+            return Err(KettleError::ValidationError(format!(
+                "comments cannot be written to {:?} config files",
+                self.format
+            )));
+        }
+        let config = self.set_or_delete(self.load_or_create()?, key, value)?;
+        let original = fs::read_to_string(self.path()).unwrap_or_default();
+        let mut comments = Comments::parse(&original);
+        comments.set(self.section_name(), key, comment);
+        let contents = format::write_ini(&Self::ordered(config, &self.order), &comments)?;
+        self.write_file(contents)
+    }
+    /// Sets value to config like `set`, returning the error instead of a `Result` for
+    /// fire-and-forget writes.
+    pub fn try_set<S: Into<String>>(
+        &self,
+        key: &'static str,
+        value: Option<S>,
+    ) -> Option<KettleError> {
+        self.set(key, value).err()
+    }
+    /// Sets any `Display` value to config. Keys with `None` values are removed.
+    pub fn set_typed<T: fmt::Display>(&self, key: &'static str, value: Option<T>) -> Result<()> {
+        self.set(key, value.map(|v| v.to_string()))
+    }
+    /// Sets an enum variant to config by its `Display` name, for reading back with `get_enum`.
+    /// Keys with `None` values are removed.
+    pub fn set_enum<T: fmt::Display>(&self, key: &'static str, value: Option<T>) -> Result<()> {
+        self.set_typed(key, value)
+    }
+    /// Sets a value spanning multiple lines.
+    ///
+    /// Lines are joined with `\n`, which is escaped on disk so the entry stays on one line;
+    /// the `ini` parser does not understand indented continuation lines. `get` returns the
+    /// joined string.
+    pub fn set_multiline(&self, key: &'static str, lines: &[&str]) -> Result<()> {
+        self.set(key, Some(lines.join("\n")))
+    }
+    /// Encrypts `value` with `passphrase` and sets the hex-encoded ciphertext to config.
+    #[cfg(feature = "encrypt")]
+    pub fn set_encrypted(&self, key: &'static str, value: &str, passphrase: &str) -> Result<()> {
+        self.set(key, Some(crate::encrypt::encrypt(value, passphrase)?))
+    }
+    /// Gets a value stored with `set_encrypted` and decrypts it with `passphrase`.
+    #[cfg(feature = "encrypt")]
+    pub fn get_decrypted(&self, key: &str, passphrase: &str) -> Result<Option<String>> {
+        match self.try_get(key)? {
+            Some(hex) => crate::encrypt::decrypt(&hex, passphrase).map(Some),
+            None => Ok(None),
+        }
+    }
+    /// Gets a secret from the platform keyring instead of the config file.
+    ///
+    /// Secrets are stored under the name of the directory holding the config file, which for
+    /// configs from `App` is the app name, and `key` as the account: `<app_name>/<key>`.
+    #[cfg(feature = "keyring")]
+    pub fn get_secret(&self, key: &str) -> Result<Option<String>> {
+        match self.keyring_entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
         }
     }
+    /// Stores a secret in the platform keyring, see `get_secret`. The config file is not
+    /// touched.
+    #[cfg(feature = "keyring")]
+    pub fn set_secret(&self, key: &str, secret: &str) -> Result<()> {
+        Ok(self.keyring_entry(key)?.set_password(secret)?)
+    }
+    #[cfg(feature = "keyring")]
+    fn keyring_entry(&self, key: &str) -> Result<keyring::Entry> {
+        let service = self.dir().file_name().unwrap_or_default().to_string_lossy();
+        Ok(keyring::Entry::new(&service, key)?)
+    }
+    /// Runs `f` over the parsed config and saves the result, in a single load-save cycle.
+    pub fn apply(&self, f: impl FnOnce(&mut Ini)) -> Result<()> {
+        let mut config = self.load_or_create()?;
+        f(&mut config);
+        self.save(config)
+    }
+    /// Creates the selected section with `defaults` if it does not exist yet. An existing
+    /// section is left untouched.
+    pub fn ensure_section(&self, defaults: &[(&'static str, &str)]) -> Result<()> {
+        let mut config = self.load_or_create()?;
+        if config.section(self.section_name()).is_some() {
+            return Ok(());
+        }
+        let section = config
+            .entry(self.section_name().map(String::from))
+            .or_insert_with(Properties::new);
+        for (key, value) in defaults {
+            section.insert(*key, *value);
+        }
+        self.save(config)
+    }
+    /// Sets binary data to config as a standard Base64 string.
+    #[cfg(feature = "base64")]
+    pub fn set_base64(&self, key: &'static str, data: &[u8]) -> Result<()> {
+        use base64::Engine;
+        self.set(
+            key,
+            Some(base64::engine::general_purpose::STANDARD.encode(data)),
+        )
+    }
+    /// Gets binary data stored with `set_base64`, failing if the value is not valid Base64.
+    #[cfg(feature = "base64")]
+    pub fn get_base64(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        use base64::Engine;
+        match self.try_get(key)? {
+            Some(value) => Ok(Some(
+                base64::engine::general_purpose::STANDARD.decode(value)?,
+            )),
+            None => Ok(None),
+        }
+    }
+    /// Serializes `value` to compact JSON and sets it to config as a single value, for
+    /// structured data that does not fit in a section.
+    #[cfg(feature = "json")]
+    pub fn set_json_value<T: serde::Serialize>(&self, key: &'static str, value: &T) -> Result<()> {
+        self.set(key, Some(serde_json::to_string(value)?))
+    }
+    /// Gets a value stored with `set_json_value`, failing if it does not deserialize as `T`.
+    #[cfg(feature = "json")]
+    pub fn get_json_value<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        match self.try_get(key)? {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+    /// Same as `set_json_value`.
+    #[cfg(feature = "json")]
+    pub fn serialize_section<T: serde::Serialize>(
+        &self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.set_json_value(key, value)
+    }
+    /// Same as `get_json_value`.
+    #[cfg(feature = "json")]
+    pub fn deserialize_section<T: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>> {
+        self.get_json_value(key)
+    }
+    /// Rewrites the config file once with sections and keys sorted alphabetically, regardless
+    /// of the ordering set with `set_ordering`.
+    pub fn write_sorted(&self) -> Result<()> {
+        self.save_ordered(self.load()?, &WriteOrder::Alphabetical)
+    }
+    /// Sets every string value of the TOML table `toml_key` in `toml_str` to the selected
+    /// section. Values of other types are skipped.
+    #[cfg(feature = "toml")]
+    pub fn import_toml_section(&self, toml_str: &str, toml_key: &str) -> Result<()> {
+        let document: toml::Table = toml_str.parse()?;
+        let table = document
+            .get(toml_key)
+            .and_then(toml::Value::as_table)
+            .ok_or_else(|| {
+                KettleError::ValidationError(format!("no TOML table named `{}`", toml_key))
+            })?;
+        self.apply(|config| {
+            for (key, value) in table {
+                if let Some(value) = value.as_str() {
+                    config.set_to(self.section_name(), key.clone(), value.to_string());
+                }
+            }
+        })
+    }
+    /// Sets a duration to config in the largest whole unit (`d`, `h`, `m` or `s`).
+    /// Sub-second precision is dropped.
+    pub fn set_duration(&self, key: &'static str, value: Duration) -> Result<()> {
+        self.set(key, Some(values::format_duration(value)))
+    }
+    /// Sets a date to config as `YYYY-MM-DD`.
+    #[cfg(feature = "chrono")]
+    pub fn set_date(&self, key: &'static str, date: chrono::NaiveDate) -> Result<()> {
+        self.set(key, Some(date.format("%Y-%m-%d").to_string()))
+    }
+    /// Sets an array to config as indexed keys `key[0]`, `key[1]`, ..., replacing any
+    /// indexed keys previously stored under `key`.
+    pub fn set_array(&self, key: &'static str, values: &[impl AsRef<str>]) -> Result<()> {
+        self.apply(|config| {
+            if let Some(properties) = config.section_mut(self.section_name()) {
+                let stale: Vec<String> = properties
+                    .iter()
+                    .map(|(k, _)| k)
+                    .filter(|k| is_index_of(k, key))
+                    .map(String::from)
+                    .collect();
+                for k in stale {
+                    properties.remove(k);
+                }
+            }
+            for (i, value) in values.iter().enumerate() {
+                let indexed = format!("{}[{}]", key, i);
+                config.set_to(self.section_name(), indexed, value.as_ref().to_string());
+            }
+        })
+    }
+    /// Sets a color to config as `#RRGGBB`.
+    pub fn set_color(&self, key: &'static str, color: (u8, u8, u8)) -> Result<()> {
+        self.set(key, Some(values::format_color(color)))
+    }
+    /// Sets a log level to config as its lowercase name.
+    #[cfg(feature = "log")]
+    pub fn set_log_level(&self, key: &'static str, level: log::Level) -> Result<()> {
+        self.set(key, Some(level.as_str().to_lowercase()))
+    }
+    /// Sets a URL to config.
+    #[cfg(feature = "url")]
+    pub fn set_url(&self, key: &'static str, url: &url::Url) -> Result<()> {
+        self.set(key, Some(url.as_str()))
+    }
+    /// Sets a socket address to config.
+    pub fn set_socket_addr(&self, key: &'static str, addr: SocketAddr) -> Result<()> {
+        self.set_typed(key, Some(addr))
+    }
+    /// Sets a UUID to config in its hyphenated form.
+    #[cfg(feature = "uuid")]
+    pub fn set_uuid(&self, key: &'static str, uuid: uuid::Uuid) -> Result<()> {
+        self.set(key, Some(uuid.hyphenated().to_string()))
+    }
+    /// Moves the value of `old_key` to `new_key`, passing it through `transform`, in a single
+    /// load-save cycle. Returns `false` without touching the file if `old_key` is not set.
+    pub fn migrate_key<F: FnOnce(String) -> String>(
+        &self,
+        old_key: &str,
+        new_key: &'static str,
+        transform: F,
+    ) -> Result<bool> {
+        let mut config = self.load_or_default()?;
+        let value = match config.delete_from(self.section_name(), old_key) {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        config.set_to(self.section_name(), new_key.to_string(), transform(value));
+        self.save(config)?;
+        Ok(true)
+    }
     fn set_or_delete<S: Into<String>>(
         &self,
         mut config: Ini,
@@ -43,36 +667,575 @@ impl Config {
         value: Option<S>,
     ) -> Result<Ini> {
         if let Some(v) = value {
-            config.set_to(self.section, key.to_string(), v.into());
+            config.set_to(self.section_name(), key.to_string(), v.into());
             Ok(config)
         } else {
-            config.delete_from(self.section, key);
+            config.delete_from(self.section_name(), key);
             Ok(config)
         }
     }
 
-    // Adds a section to a config query.
+    /// Writes `template` to the config file, creating directories as needed, unless the file
+    /// already exists.
+    pub fn create_from_template(&self, template: &str) -> Result<()> {
+        dirs::create_dir_all(self.dir())?;
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.path())
+        {
+            Ok(mut file) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(file = %self.path().display(), "creating config file from template");
+                Ok(file.write_all(template.as_bytes())?)
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replaces the config file with `content`, which must be valid `ini`. Directories are
+    /// created as needed. `ini` files get `content` verbatim, comments included; other formats
+    /// are converted.
+    pub fn import_ini_string(&self, content: &str) -> Result<()> {
+        let config = Ini::load_from_str(content)?;
+        dirs::create_dir_all(self.dir())?;
+        let contents = if self.format == Format::Ini {
+            content.to_string()
+        } else {
+            self.format.write(&Self::ordered(config, &self.order), "")?
+        };
+        self.write_file(contents)
+    }
+
+    /// Copies the config file's contents to `dest`, creating parent directories as needed.
+    pub fn write_to_path(&self, dest: &Path) -> Result<()> {
+        let contents = fs::read(self.path())?;
+        if let Some(parent) = dest.parent() {
+            dirs::create_dir_all(parent)?;
+        }
+        fs::write(dest, contents)?;
+        Ok(())
+    }
+
+    /// Removes the config file from disk. Succeeds if the file does not exist.
+    pub fn delete(&self) -> Result<()> {
+        match fs::remove_file(self.path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Last modification time of the config file, or `None` if it does not exist.
+    pub fn file_modified_time(&self) -> Result<Option<SystemTime>> {
+        match fs::metadata(self.path()).and_then(|m| m.modified()) {
+            Ok(time) => Ok(Some(time)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Renames the config file within its directory and returns a handle to the new file.
+    ///
+    /// Fails with an `AlreadyExists` I/O error if a file named `new_name` already exists. The
+    /// returned handle keeps this handle's section, ordering and format. This handle should
+    /// not be used afterwards.
+    pub fn rename_file(&self, new_name: &'static str) -> Result<Config> {
+        let path = self.dir().join(new_name);
+        match fs::symlink_metadata(&path) {
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("config file {} already exists", path.display()),
+                )
+                .into())
+            }
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            Err(_) => {}
+        }
+        fs::rename(self.path(), &path)?;
+        let mut renamed = Config::from_file(path);
+        renamed.section = self.section.clone();
+        renamed.order = self.order.clone();
+        renamed.format = self.format;
+        renamed.sanitize = self.sanitize;
+        Ok(renamed)
+    }
+
+    /// Collects the `PREFIX_KEY` environment variables into a map of lowercased keys, without
+    /// reading any file. The prefix is matched in uppercase, like `merge_env`, and variables
+    /// that are not valid Unicode are skipped.
+    pub fn from_env(prefix: &str) -> Result<HashMap<String, String>> {
+        if prefix.is_empty() {
+            return Err(KettleError::ValidationError(
+                "environment prefix must not be empty".to_string(),
+            ));
+        }
+        let prefix = format!("{}_", prefix.to_uppercase());
+        Ok(std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .filter_map(|(key, value)| {
+                let key = key.strip_prefix(&prefix).filter(|k| !k.is_empty())?;
+                Some((key.to_lowercase(), value))
+            })
+            .collect())
+    }
+
+    /// Snapshot of this config where `PREFIX_SECTION_KEY` environment variables override
+    /// file values. The returned config is read-only.
+    pub fn merge_env(&self, prefix: &str) -> Result<MergedConfig> {
+        Ok(MergedConfig::new(
+            self.load_or_default()?,
+            prefix,
+            self.section.clone(),
+            self.sanitize,
+        ))
+    }
+
+    /// Serializes the selected section's keys as a TOML table, without a `[header]` line.
+    #[cfg(feature = "toml")]
+    pub fn export_toml_section(&self) -> Result<String> {
+        let config = self.load_or_default()?;
+        let table: toml::Table = config
+            .section(self.section_name())
+            .into_iter()
+            .flat_map(|properties| properties.iter())
+            .map(|(key, value)| (key.to_string(), toml::Value::String(self.value(value))))
+            .collect();
+        Ok(toml::to_string(&table)?)
+    }
+
+    /// Entries of the selected section as `(PREFIX_KEY, value)` pairs, or `PREFIX_SECTION_KEY`
+    /// when a section is set, ready for `std::process::Command::envs`.
+    pub fn as_env_block(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let config = self.load_or_default()?;
+        Ok(config
+            .section(self.section_name())
+            .into_iter()
+            .flat_map(|properties| properties.iter())
+            .map(|(key, value)| (env_key(prefix, self.section_name(), key), self.value(value)))
+            .collect())
+    }
+
+    /// Loads the config into shared memory and keeps it in sync with the file on disk.
+    ///
+    /// A background thread polls the file's modification time and re-parses it when it
+    /// changes. If the new contents cannot be read, the previous value is kept until the next
+    /// change and the error is logged as a warning through `tracing` and `log`, if those
+    /// features are enabled. The thread exits once every clone of the returned `Arc` has been dropped.
+    pub fn watch_and_reload(&self) -> Result<Arc<RwLock<Ini>>> {
+        fn modified(path: &Path) -> Option<SystemTime> {
+            fs::metadata(path).and_then(|m| m.modified()).ok()
+        }
+        let path = self.path.clone();
+        let format = self.format;
+        let mut last_modified = modified(&path);
+        let shared = Arc::new(RwLock::new(self.load_or_default()?));
+        let weak = Arc::downgrade(&shared);
+        thread::spawn(move || loop {
+            thread::sleep(WATCH_INTERVAL);
+            let shared = match weak.upgrade() {
+                Some(shared) => shared,
+                None => break,
+            };
+            let current = modified(&path);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            let reloaded = fs::read_to_string(&path)
+                .map_err(KettleError::from)
+                .and_then(|text| format.parse(&text));
+            match reloaded {
+                Ok(config) => *shared.write().unwrap_or_else(PoisonError::into_inner) = config,
+                #[cfg_attr(not(any(feature = "tracing", feature = "log")), allow(unused))]
+                Err(e) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(file = %path.display(), error = %e, "could not reload config");
+                    #[cfg(feature = "log")]
+                    log::warn!("could not reload config {}: {}", path.display(), e);
+                }
+            }
+        });
+        Ok(shared)
+    }
+
+    /// The whole config as `ini` text, whatever the file's format. A missing file gives an
+    /// empty string.
+    pub fn to_ini_string(&self) -> Result<String> {
+        let mut buf = Vec::new();
+        self.load_or_default()?.write_to(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    /// Prints the config's sections, keys and values to stderr, for debugging.
+    ///
+    /// Read errors are reported on stderr rather than returned. With the `color` feature the
+    /// output is colored with ANSI escape codes.
+    pub fn print_debug(&self) {
+        match self.load() {
+            Ok(config) => crate::debug::print(self.path(), &config),
+            Err(e) => eprintln!("could not read config {}: {}", self.path().display(), e),
+        }
+    }
+
+    /// Adds a section to a config query.
     pub fn section(mut self, section: &'static str) -> Self {
-        self.section = Some(section);
+        self.section = Some(Cow::Borrowed(section));
+        self
+    }
+
+    /// Adds a section to a config query, for section names only known at runtime.
+    pub fn section_owned(mut self, section: String) -> Self {
+        self.section = Some(Cow::Owned(section));
+        self
+    }
+    fn section_name(&self) -> Option<&str> {
+        self.section.as_deref()
+    }
+
+    /// Strips control characters from values read through this handle, see `sanitize_value`.
+    pub fn sanitize(mut self) -> Self {
+        self.sanitize = true;
         self
     }
 
+    /// Removes control characters, such as terminal escape sequences, tabs and newlines,
+    /// from `raw`.
+    pub fn sanitize_value(raw: &str) -> String {
+        raw.chars().filter(|c| !c.is_control()).collect()
+    }
+    fn value(&self, raw: &str) -> String {
+        if self.sanitize {
+            Self::sanitize_value(raw)
+        } else {
+            raw.to_string()
+        }
+    }
+
+    /// Reads and writes the file as `format`, instead of the format inferred from its
+    /// extension.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the order in which sections and keys are written on save.
+    pub fn set_ordering(mut self, order: WriteOrder) -> Self {
+        self.order = order;
+        self
+    }
+    fn ordered(config: Ini, order: &WriteOrder) -> Ini {
+        let mut sections: Vec<(Option<&str>, &Properties)> = config.iter().collect();
+        match order {
+            WriteOrder::Insertion => return config,
+            WriteOrder::Alphabetical => sections.sort_by_key(|(section, _)| *section),
+            WriteOrder::Manual(order) => sections.sort_by_key(|(section, _)| {
+                section.map(|s| order.iter().position(|o| *o == s).unwrap_or(order.len()))
+            }),
+        }
+        // sections that appear more than once are merged before their keys are sorted
+        let mut merged: Vec<(Option<&str>, Vec<_>)> = Vec::new();
+        for (section, properties) in sections {
+            match merged.iter().position(|(s, _)| *s == section) {
+                Some(i) => merged[i].1.extend(properties.iter()),
+                None => merged.push((section, properties.iter().collect())),
+            }
+        }
+        let mut ordered = Ini::new();
+        for (section, mut keys) in merged {
+            if *order == WriteOrder::Alphabetical {
+                keys.sort_by_key(|(key, _)| *key);
+            }
+            let entry = ordered
+                .entry(section.map(String::from))
+                .or_insert_with(Properties::new);
+            for (key, value) in keys {
+                entry.append(key, value);
+            }
+        }
+        ordered
+    }
+
     // std::fs
-    fn path(&self) -> PathBuf {
-        self.path.0.join(self.path.1)
+    fn path(&self) -> &Path {
+        &self.path
+    }
+    fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new(""))
     }
     fn load(&self) -> Result<Ini> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(file = %self.path().display(), "reading config file");
         let file_str = fs::read_to_string(self.path())?;
-        let config = Ini::load_from_str(&file_str)?;
+        let config = self.format.parse(&file_str)?;
         Ok(config)
     }
+    fn load_or_default(&self) -> Result<Ini> {
+        match self.load() {
+            Err(KettleError::IoError(e)) if e.kind() == io::ErrorKind::NotFound => Ok(Ini::new()),
+            result => result,
+        }
+    }
+    fn load_or_create(&self) -> Result<Ini> {
+        match self.load() {
+            Err(KettleError::IoError(e)) if e.kind() == io::ErrorKind::NotFound => {
+                self.create_empty()?;
+                Ok(Ini::new())
+            }
+            result => result,
+        }
+    }
     fn save(&self, config: Ini) -> Result<()> {
-        config.write_to_file(self.path())?;
+        self.save_ordered(config, &self.order)
+    }
+    fn save_ordered(&self, config: Ini, order: &WriteOrder) -> Result<()> {
+        let original = fs::read_to_string(self.path()).unwrap_or_default();
+        let contents = self
+            .format
+            .write(&Self::ordered(config, order), &original)?;
+        self.write_file(contents)
+    }
+    fn write_file(&self, contents: String) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(file = %self.path().display(), len = contents.len(), "writing config file");
+        atomic::write(self.path(), contents)?;
         Ok(())
     }
     fn create_empty(&self) -> Result<()> {
-        std::fs::create_dir_all(self.path.0.clone())?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(dir = %self.dir().display(), file = %self.path().display(), "creating config file");
+        dirs::create_dir_all(self.dir())?;
         std::fs::write(self.path(), b"")?;
         Ok(())
     }
 }
+
+/// Whether `key` has the form `array[<index>]`.
+fn is_index_of(key: &str, array: &str) -> bool {
+    key.strip_prefix(array)
+        .and_then(|rest| rest.strip_prefix('['))
+        .and_then(|rest| rest.strip_suffix(']'))
+        .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Config>();
+};
+
+impl fmt::Debug for Config {
+    /// Shows where the handle points without reading the file, so values are never printed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("path", &self.path)
+            .field("section", &self.section)
+            .field("order", &self.order)
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Config {
+    /// Formats the handle as `Config { path: "...", section: "..." }`, leaving out the section
+    /// if none is selected. Use `to_ini_string` for the file contents.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Config {{ path: {:?}", self.path)?;
+        if let Some(section) = &self.section {
+            write!(f, ", section: {:?}", section)?;
+        }
+        f.write_str(" }")
+    }
+}
+
+impl IntoIterator for &Config {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    /// Iterates over the entries of the selected section. An unreadable file yields nothing.
+    fn into_iter(self) -> Self::IntoIter {
+        let config = self.load().unwrap_or_default();
+        let entries: Vec<(String, String)> = config
+            .section(self.section_name())
+            .into_iter()
+            .flat_map(|properties| properties.iter())
+            .map(|(key, value)| (key.to_string(), self.value(value)))
+            .collect();
+        entries.into_iter()
+    }
+}
+
+impl IntoIterator for Config {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        (&self).into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A handle to `file` in a fresh scratch directory.
+    fn scratch(name: &str, file: &str) -> Config {
+        let dir =
+            std::env::temp_dir().join(format!("kettle-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Config::from_file(dir.join(file))
+    }
+
+    #[test]
+    fn set_keeps_unparsable_file() {
+        let config = scratch("unparsable", "config.ini");
+        let original = "; settings\n[b] ; note\nk = 1\n";
+        fs::write(config.path(), original).unwrap();
+        assert!(config.set("k", Some("2")).is_err());
+        assert_eq!(fs::read_to_string(config.path()).unwrap(), original);
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn set_keeps_json_document_that_is_not_an_object() {
+        let config = scratch("json-array", "config.json");
+        fs::write(config.path(), "[1, 2]").unwrap();
+        assert!(matches!(
+            config.set("a", Some("1")),
+            Err(KettleError::InvalidDocument)
+        ));
+        assert_eq!(fs::read_to_string(config.path()).unwrap(), "[1, 2]");
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+
+    #[test]
+    fn rename_file_does_not_overwrite() {
+        let a = scratch("rename", "a.ini");
+        let b = Config::from_file(a.dir().join("b.ini"));
+        a.set("profile", Some("a")).unwrap();
+        b.set("profile", Some("b")).unwrap();
+        assert!(matches!(
+            a.rename_file("b.ini"),
+            Err(KettleError::IoError(e)) if e.kind() == io::ErrorKind::AlreadyExists
+        ));
+        assert_eq!(a.get("profile"), Some("a".to_string()));
+        assert_eq!(b.get("profile"), Some("b".to_string()));
+
+        let c = a.rename_file("c.ini").unwrap();
+        assert_eq!(c.get("profile"), Some("a".to_string()));
+        assert!(!a.path().exists());
+        fs::remove_dir_all(a.dir()).unwrap();
+    }
+
+    #[test]
+    fn sanitize_applies_to_every_read() {
+        let config = scratch("sanitize", "config.ini").sanitize();
+        fs::write(
+            config.path(),
+            "a = x\\ty\nlist[0] = x\\ty\n[s]\nb = x\\ty\n",
+        )
+        .unwrap();
+        let clean = Some("xy".to_string());
+        assert_eq!(config.get("a"), clean);
+        assert_eq!(config.get_with_fallback(&["missing", "a"]), clean);
+        assert_eq!(config.get_array("list"), vec!["xy".to_string()]);
+        assert_eq!(config.get_dotted("s.b"), clean);
+        assert_eq!(
+            config.get_from_sections("b", &["s"]),
+            vec![("s".to_string(), clean.clone())]
+        );
+        assert_eq!(
+            config.merge_env("KETTLE_SANITIZE_TEST").unwrap().get("a"),
+            clean
+        );
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+
+    #[test]
+    fn alphabetical_order_sorts_sections_and_keys() {
+        let config = scratch("alphabetical", "config.ini").set_ordering(WriteOrder::Alphabetical);
+        fs::write(
+            config.path(),
+            "; about zeta\nzeta=1\nalpha=2\n\n; section b\n[b]\ny=1\n; about x\nx=2\n\n[a]\nk=v\n",
+        )
+        .unwrap();
+        config.set("beta", Some("3")).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.path()).unwrap(),
+            "alpha=2\nbeta=3\n; about zeta\nzeta=1\n\n[a]\nk=v\n\n; section b\n[b]\n; about x\nx=2\ny=1\n"
+        );
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+
+    #[test]
+    fn manual_order_puts_listed_sections_first() {
+        let config = scratch("manual", "config.ini")
+            .set_ordering(WriteOrder::Manual(vec!["c", "a"]))
+            .section("b");
+        fs::write(
+            config.path(),
+            "g=1\n\n[a]\nk=1\n\n; section b\n[b]\nz=1\ny=2\n\n; section c\n[c]\nk=3\n",
+        )
+        .unwrap();
+        config.set("x", Some("3")).unwrap();
+        assert_eq!(
+            fs::read_to_string(config.path()).unwrap(),
+            "g=1\n\n; section c\n[c]\nk=3\n\n[a]\nk=1\n\n; section b\n[b]\nz=1\ny=2\nx=3\n"
+        );
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+
+    #[test]
+    fn ordering_merges_duplicate_sections() {
+        let config = scratch("duplicates", "config.ini").set_ordering(WriteOrder::Alphabetical);
+        fs::write(config.path(), "[b]\nk=1\n\n[a]\nk=2\n\n[b]\nj=3\n").unwrap();
+        config.write_sorted().unwrap();
+        assert_eq!(
+            fs::read_to_string(config.path()).unwrap(),
+            "[a]\nk=2\n\n[b]\nj=3\nk=1\n"
+        );
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+
+    #[test]
+    fn recognizes_array_indices() {
+        assert!(is_index_of("key[0]", "key"));
+        assert!(is_index_of("key[12]", "key"));
+        assert!(!is_index_of("key[x]", "key"));
+        assert!(!is_index_of("key[]", "key"));
+        assert!(!is_index_of("key[-1]", "key"));
+        assert!(!is_index_of("keyx[0]", "key"));
+        assert!(!is_index_of("key", "key"));
+        assert!(!is_index_of("key[0]x", "key"));
+    }
+
+    #[test]
+    fn shrinking_an_array_removes_stale_entries() {
+        let config = scratch("array-shrink", "config.ini").section("list");
+        config.set_array("key", &["a", "b", "c", "d"]).unwrap();
+        config.set("key[x]", Some("kept")).unwrap();
+        config.set("keyx[0]", Some("kept")).unwrap();
+        config.set_array("key", &["e", "f"]).unwrap();
+        assert_eq!(config.get_array("key"), vec!["e", "f"]);
+        assert_eq!(config.get("key[2]"), None);
+        assert_eq!(config.get("key[3]"), None);
+        assert_eq!(config.get("key[x]"), Some("kept".to_string()));
+        assert_eq!(config.get("keyx[0]"), Some("kept".to_string()));
+
+        config.set_array("key", &[] as &[&str]).unwrap();
+        assert!(config.get_array("key").is_empty());
+        assert_eq!(config.count_keys().unwrap(), 2);
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+
+    #[test]
+    fn get_array_stops_at_first_gap() {
+        let config = scratch("array-gap", "config.ini");
+        fs::write(config.path(), "key[0] = a\nkey[1] = b\nkey[3] = d\n").unwrap();
+        assert_eq!(config.get_array("key"), vec!["a", "b"]);
+        assert!(config.get_array("missing").is_empty());
+        fs::remove_dir_all(config.dir()).unwrap();
+    }
+}
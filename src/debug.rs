@@ -0,0 +1,42 @@
+//! Human-readable config dumps for debugging.
+
+use ini::Ini;
+use std::path::Path;
+
+const BOLD: &str = "1";
+const SECTION: &str = "1;34";
+const KEY: &str = "32";
+
+#[cfg(feature = "color")]
+fn paint(text: &str, style: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", style, text)
+}
+
+#[cfg(not(feature = "color"))]
+fn paint(text: &str, _style: &str) -> String {
+    text.to_string()
+}
+
+/// Prints every section of `config` to stderr as an aligned `key = value` table.
+pub(crate) fn print(path: &Path, config: &Ini) {
+    eprintln!("{}", paint(&path.display().to_string(), BOLD));
+    for (section, properties) in config.iter() {
+        if section.is_none() && properties.is_empty() {
+            continue;
+        }
+        let header = match section {
+            Some(name) => format!("[{}]", name),
+            None => "(global)".to_string(),
+        };
+        eprintln!("{}", paint(&header, SECTION));
+        let width = properties
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (key, value) in properties.iter() {
+            let key = format!("{:width$}", key, width = width);
+            eprintln!("  {} = {}", paint(&key, KEY), value);
+        }
+    }
+}
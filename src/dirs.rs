@@ -3,6 +3,9 @@
 //! PathBufs are automatically unwrapped if dirs are infallible on all platforms.
 
 use dirs;
+use std::env;
+use std::fs;
+use std::io;
 use std::path::*;
 
 pub fn home_dir() -> PathBuf {
@@ -72,3 +75,38 @@ pub fn template_dir() -> Option<PathBuf> {
 pub fn video_dir() -> Option<PathBuf> {
     dirs::video_dir()
 }
+
+/// Directories listed in `$XDG_CONFIG_DIRS`, in order of preference.
+///
+/// Defaults to `/etc/xdg` on Unix-like platforms and to no directories elsewhere.
+pub fn system_config_dirs() -> Vec<PathBuf> {
+    match env::var_os("XDG_CONFIG_DIRS") {
+        Some(dirs) if !dirs.is_empty() => env::split_paths(&dirs)
+            .filter(|dir| dir.is_absolute())
+            .collect(),
+        _ if cfg!(unix) => vec![PathBuf::from("/etc/xdg")],
+        _ => Vec::new(),
+    }
+}
+
+/// `$XDG_STATE_HOME`, or `$HOME/.local/state` if it is unset or not absolute.
+///
+/// Windows and macOS have no state directory, so this returns `None` there.
+pub fn state_dir() -> Option<PathBuf> {
+    if cfg!(any(windows, target_os = "macos")) {
+        return None;
+    }
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local/state")))
+}
+
+/// `fs::create_dir_all`, emitting a `tracing` event when `dir` does not exist yet.
+pub(crate) fn create_dir_all(dir: &Path) -> io::Result<()> {
+    #[cfg(feature = "tracing")]
+    if !dir.is_dir() {
+        tracing::debug!(dir = %dir.display(), "creating directory");
+    }
+    fs::create_dir_all(dir)
+}
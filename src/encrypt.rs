@@ -0,0 +1,113 @@
+//! Passphrase-based value encryption.
+//!
+//! Values are encrypted with AES-256-GCM under a key derived from the passphrase with
+//! PBKDF2-HMAC-SHA256 and a random salt. The stored form is the hex encoding of
+//! `salt || nonce || ciphertext`.
+
+use crate::error::*;
+use aes_gcm::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sha2::Sha256;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+pub(crate) fn encrypt(value: &str, passphrase: &str) -> Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(passphrase, &salt)
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| KettleError::EncryptionError("value could not be encrypted".to_string()))?;
+
+    let mut out = String::with_capacity(2 * (SALT_LEN + NONCE_LEN + ciphertext.len()));
+    for byte in salt.iter().chain(nonce.iter()).chain(ciphertext.iter()) {
+        out.push_str(&format!("{:02x}", byte));
+    }
+    Ok(out)
+}
+
+pub(crate) fn decrypt(hex: &str, passphrase: &str) -> Result<String> {
+    let invalid = || KettleError::EncryptionError("value is not valid encrypted data".to_string());
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<std::result::Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    if bytes.len() < SALT_LEN + NONCE_LEN {
+        return Err(invalid());
+    }
+    let (salt, rest) = bytes.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let plaintext = cipher(passphrase, salt)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            KettleError::EncryptionError("wrong passphrase or corrupted value".to_string())
+        })?;
+    String::from_utf8(plaintext).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let hex = encrypt("s3cret value", "passphrase").unwrap();
+        assert_eq!(decrypt(&hex, "passphrase").unwrap(), "s3cret value");
+        assert_eq!(decrypt(&encrypt("", "pass").unwrap(), "pass").unwrap(), "");
+    }
+
+    #[test]
+    fn uses_a_fresh_salt_and_nonce() {
+        assert_ne!(
+            encrypt("value", "pass").unwrap(),
+            encrypt("value", "pass").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let hex = encrypt("value", "right").unwrap();
+        assert!(matches!(
+            decrypt(&hex, "wrong"),
+            Err(KettleError::EncryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_hex() {
+        let hex = encrypt("value", "pass").unwrap();
+        for bad in [
+            &hex[1..],
+            "zz",
+            "",
+            "é",
+            &hex[..2 * (SALT_LEN + NONCE_LEN) - 2],
+        ] {
+            assert!(
+                matches!(decrypt(bad, "pass"), Err(KettleError::EncryptionError(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_ciphertext() {
+        let mut hex = encrypt("value", "pass").unwrap();
+        let last = if hex.ends_with('0') { "1" } else { "0" };
+        hex.replace_range(hex.len() - 1.., last);
+        assert!(decrypt(&hex, "pass").is_err());
+    }
+}
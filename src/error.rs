@@ -3,7 +3,10 @@ use thiserror::Error;
 pub type Result<T> = std::result::Result<T, KettleError>;
 
 /// KettleError enumerates all possible errors returned by this library.
+///
+/// Some variants only exist with the matching cargo feature, so matches need a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum KettleError {
     /// Represents all other `std::io` errors.
     #[error(transparent)]
@@ -16,4 +19,60 @@ pub enum KettleError {
     /// Represents all other `ini` Errors;
     #[error(transparent)]
     IniError(#[from] ini::ini::Error),
+
+    /// Represents all `toml` parsing errors.
+    #[cfg(feature = "toml")]
+    #[error(transparent)]
+    TomlParseError(#[from] toml::de::Error),
+
+    /// Represents all `toml` serialization errors.
+    #[cfg(feature = "toml")]
+    #[error(transparent)]
+    TomlWriteError(#[from] toml::ser::Error),
+
+    /// Represents all `json` errors.
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
+    /// Represents a value that is not valid Base64.
+    #[cfg(feature = "base64")]
+    #[error(transparent)]
+    Base64Error(#[from] base64::DecodeError),
+
+    /// Represents a value that is not a valid URL.
+    #[cfg(feature = "url")]
+    #[error(transparent)]
+    InvalidUrl(#[from] url::ParseError),
+
+    /// Represents errors from the platform keyring.
+    #[cfg(feature = "keyring")]
+    #[error(transparent)]
+    KeyringError(#[from] keyring::Error),
+
+    /// Represents errors resolving XDG base directories.
+    #[cfg(all(unix, feature = "xdg"))]
+    #[error(transparent)]
+    XdgError(#[from] xdg::BaseDirectoriesError),
+
+    /// Represents a value that could not be encrypted or decrypted.
+    #[cfg(feature = "encrypt")]
+    #[error("encryption error: {0}")]
+    EncryptionError(String),
+
+    /// Represents a config document that cannot be mapped onto sections and keys.
+    #[error("config document must be a table of sections and keys")]
+    InvalidDocument,
+
+    /// Represents a config value that failed validation.
+    #[error("{0}")]
+    ValidationError(String),
+
+    /// Represents a config value that could not be parsed into the requested type.
+    #[error("could not parse `{value}` for key `{key}` as {type_name}")]
+    ParseError {
+        key: String,
+        value: String,
+        type_name: &'static str,
+    },
 }
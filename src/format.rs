@@ -0,0 +1,341 @@
+//! Config file formats.
+//!
+//! `ini` files keep their comments and blank lines on save, see `Comments`.
+//!
+//! Every other format is read into an `Ini`: top-level values go to the global section and
+//! top-level tables become sections. On save, values are written back with the type they
+//! had in the existing file where possible, and nested tables that an `Ini` cannot hold
+//! are carried over untouched.
+
+use crate::{comments::Comments, error::*};
+use ini::Ini;
+use std::path::Path;
+
+/// File format of a config file, inferred from its extension.
+///
+/// Variants are added by the `toml` and `json` features, so matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    Ini,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl Format {
+    /// Infers the format from a file extension, falling back to `Ini` for unknown extensions.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_extension)
+            .unwrap_or(Format::Ini)
+    }
+
+    /// Maps a file extension onto a known format.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "ini" => Some(Format::Ini),
+            #[cfg(feature = "toml")]
+            "toml" => Some(Format::Toml),
+            #[cfg(feature = "json")]
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn parse(self, text: &str) -> Result<Ini> {
+        match self {
+            Format::Ini => Ok(Ini::load_from_str(text)?),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml_format::parse(text),
+            #[cfg(feature = "json")]
+            Format::Json => json_format::parse(text),
+        }
+    }
+
+    /// Serializes `config`, using `original` (the current file contents) to keep comments,
+    /// value types and unsupported entries.
+    pub(crate) fn write(self, config: &Ini, original: &str) -> Result<String> {
+        match self {
+            Format::Ini => write_ini(config, &Comments::parse(original)),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml_format::write(config, original),
+            #[cfg(feature = "json")]
+            Format::Json => json_format::write(config, original),
+        }
+    }
+}
+
+/// Serializes `config` as `ini`, re-inserting `comments`.
+pub(crate) fn write_ini(config: &Ini, comments: &Comments) -> Result<String> {
+    let mut buf = Vec::new();
+    config.write_to(&mut buf)?;
+    Ok(comments.apply(&String::from_utf8_lossy(&buf)))
+}
+
+#[cfg(feature = "toml")]
+mod toml_format {
+    use crate::error::*;
+    use ini::{ini::Properties, Ini};
+    use std::mem::discriminant;
+    use toml::{Table, Value};
+
+    pub(super) fn parse(text: &str) -> Result<Ini> {
+        let table: Table = text.parse()?;
+        let mut config = Ini::new();
+        for (key, value) in &table {
+            if let Value::Table(section) = value {
+                config
+                    .entry(Some(key.clone()))
+                    .or_insert_with(Properties::new);
+                for (k, v) in section {
+                    if let Some(v) = to_ini_value(v) {
+                        config.set_to(Some(key.as_str()), k.clone(), v);
+                    }
+                }
+            } else if let Some(v) = to_ini_value(value) {
+                config.set_to(None::<&str>, key.clone(), v);
+            }
+        }
+        Ok(config)
+    }
+
+    pub(super) fn write(config: &Ini, original: &str) -> Result<String> {
+        let original: Table = original.parse()?;
+        let mut table = Table::new();
+        for (section, properties) in config.iter() {
+            match section {
+                None => {
+                    for (k, v) in properties.iter() {
+                        table.insert(k.to_string(), from_ini_value(v, original.get(k)));
+                    }
+                }
+                Some(name) => {
+                    let original = original.get(name).and_then(Value::as_table);
+                    let mut section = Table::new();
+                    for (k, v) in original.into_iter().flatten() {
+                        if v.is_table() {
+                            section.insert(k.clone(), v.clone());
+                        }
+                    }
+                    for (k, v) in properties.iter() {
+                        let typed = from_ini_value(v, original.and_then(|t| t.get(k)));
+                        section.insert(k.to_string(), typed);
+                    }
+                    table.insert(name.to_string(), Value::Table(section));
+                }
+            }
+        }
+        Ok(toml::to_string(&table)?)
+    }
+
+    fn to_ini_value(value: &Value) -> Option<String> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Table(_) => None,
+            other => Some(other.to_string()),
+        }
+    }
+
+    fn from_ini_value(value: &str, original: Option<&Value>) -> Value {
+        let string = Value::String(value.to_string());
+        match original {
+            Some(Value::String(_)) | None => string,
+            Some(original) => format!("v = {}", value)
+                .parse::<Table>()
+                .ok()
+                .and_then(|mut t| t.remove("v"))
+                .filter(|v| discriminant(v) == discriminant(original))
+                .unwrap_or(string),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+mod json_format {
+    use crate::error::*;
+    use ini::{ini::Properties, Ini};
+    use serde_json::{Map, Value};
+    use std::mem::discriminant;
+
+    fn object(text: &str) -> Result<Map<String, Value>> {
+        if text.trim().is_empty() {
+            return Ok(Map::new());
+        }
+        match serde_json::from_str(text)? {
+            Value::Object(map) => Ok(map),
+            _ => Err(KettleError::InvalidDocument),
+        }
+    }
+
+    pub(super) fn parse(text: &str) -> Result<Ini> {
+        let mut config = Ini::new();
+        for (key, value) in &object(text)? {
+            if let Value::Object(section) = value {
+                config
+                    .entry(Some(key.clone()))
+                    .or_insert_with(Properties::new);
+                for (k, v) in section {
+                    if let Some(v) = to_ini_value(v) {
+                        config.set_to(Some(key.as_str()), k.clone(), v);
+                    }
+                }
+            } else if let Some(v) = to_ini_value(value) {
+                config.set_to(None::<&str>, key.clone(), v);
+            }
+        }
+        Ok(config)
+    }
+
+    pub(super) fn write(config: &Ini, original: &str) -> Result<String> {
+        let original = object(original)?;
+        let mut map = Map::new();
+        for (section, properties) in config.iter() {
+            match section {
+                None => {
+                    for (k, v) in properties.iter() {
+                        map.insert(k.to_string(), from_ini_value(v, original.get(k)));
+                    }
+                }
+                Some(name) => {
+                    let original = original.get(name).and_then(Value::as_object);
+                    let mut section = Map::new();
+                    for (k, v) in original.into_iter().flatten() {
+                        if v.is_object() {
+                            section.insert(k.clone(), v.clone());
+                        }
+                    }
+                    for (k, v) in properties.iter() {
+                        let typed = from_ini_value(v, original.and_then(|o| o.get(k)));
+                        section.insert(k.to_string(), typed);
+                    }
+                    map.insert(name.to_string(), Value::Object(section));
+                }
+            }
+        }
+        Ok(serde_json::to_string_pretty(&Value::Object(map))?)
+    }
+
+    fn to_ini_value(value: &Value) -> Option<String> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Object(_) => None,
+            other => Some(other.to_string()),
+        }
+    }
+
+    fn from_ini_value(value: &str, original: Option<&Value>) -> Value {
+        let string = Value::String(value.to_string());
+        match original {
+            Some(Value::String(_)) | None => string,
+            Some(original) => serde_json::from_str::<Value>(value)
+                .ok()
+                .filter(|v| discriminant(v) == discriminant(original))
+                .unwrap_or(string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_format_from_extension() {
+        assert_eq!(Format::from_path("config"), Format::Ini);
+        assert_eq!(Format::from_path("config.ini"), Format::Ini);
+        assert_eq!(Format::from_extension("yaml"), None);
+        #[cfg(feature = "toml")]
+        assert_eq!(Format::from_path("config.toml"), Format::Toml);
+        #[cfg(feature = "json")]
+        assert_eq!(Format::from_path("config.json"), Format::Json);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trip_keeps_types_and_nested_tables() {
+        let original = "port = 8080\nname = \"kettle\"\n\n[server]\nenabled = true\nratio = 0.5\n\n[server.tls]\ncert = \"a.pem\"\n";
+        let mut config = Format::Toml.parse(original).unwrap();
+        assert_eq!(config.get_from(None::<&str>, "port"), Some("8080"));
+        assert_eq!(config.get_from(Some("server"), "enabled"), Some("true"));
+        assert_eq!(config.get_from(Some("server"), "tls"), None);
+
+        config.set_to(Some("server"), "enabled".to_string(), "false".to_string());
+        config.set_to(Some("server"), "host".to_string(), "localhost".to_string());
+        let written: toml::Table = Format::Toml
+            .write(&config, original)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(written["port"], toml::Value::Integer(8080));
+        assert_eq!(written["name"], toml::Value::String("kettle".to_string()));
+        let server = written["server"].as_table().unwrap();
+        assert_eq!(server["enabled"], toml::Value::Boolean(false));
+        assert_eq!(server["ratio"], toml::Value::Float(0.5));
+        assert_eq!(server["host"], toml::Value::String("localhost".to_string()));
+        assert_eq!(server["tls"]["cert"].as_str(), Some("a.pem"));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_value_of_another_type_is_written_as_string() {
+        let original = "port = 8080\n";
+        let mut config = Format::Toml.parse(original).unwrap();
+        config.set_to(None::<&str>, "port".to_string(), "auto".to_string());
+        let written: toml::Table = Format::Toml
+            .write(&config, original)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(written["port"], toml::Value::String("auto".to_string()));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip_keeps_types_and_nested_objects() {
+        let original = r#"{"port": 8080, "tags": [1, 2], "server": {"enabled": true, "tls": {"cert": "a.pem"}}}"#;
+        let mut config = Format::Json.parse(original).unwrap();
+        assert_eq!(config.get_from(None::<&str>, "tags"), Some("[1,2]"));
+        assert_eq!(config.get_from(Some("server"), "tls"), None);
+
+        config.set_to(None::<&str>, "port".to_string(), "9090".to_string());
+        config.set_to(Some("server"), "enabled".to_string(), "false".to_string());
+        let written: serde_json::Value =
+            serde_json::from_str(&Format::Json.write(&config, original).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            serde_json::json!({
+                "port": 9090,
+                "tags": [1, 2],
+                "server": {"enabled": false, "tls": {"cert": "a.pem"}}
+            })
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_value_of_another_type_is_written_as_string() {
+        let original = r#"{"enabled": true}"#;
+        let mut config = Format::Json.parse(original).unwrap();
+        config.set_to(None::<&str>, "enabled".to_string(), "sometimes".to_string());
+        let written: serde_json::Value =
+            serde_json::from_str(&Format::Json.write(&config, original).unwrap()).unwrap();
+        assert_eq!(written, serde_json::json!({"enabled": "sometimes"}));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_document_must_be_an_object() {
+        assert!(matches!(
+            Format::Json.parse("[1, 2]"),
+            Err(KettleError::InvalidDocument)
+        ));
+        assert!(matches!(
+            Format::Json.write(&Ini::new(), "[1, 2]"),
+            Err(KettleError::InvalidDocument)
+        ));
+    }
+}
@@ -2,7 +2,7 @@
 //! It currently provides...
 //!
 //! - app-specific `dirs`
-//! - easy `ini` config files
+//! - easy `ini` config files (optionally `toml` and `json`)
 //!
 //! This crate utilizes the [`dirs`](https://crates.io/crates/dirs) crate and re-exports it for easy access.
 //!
@@ -26,7 +26,7 @@
 //!     // named config file
 //!     THIS_APP.config_file("admin_profiles")
 //!         .section("dev") // setting `ini` sections is possible
-//!         .set("view", None);
+//!         .set("view", None::<&str>);
 //!
 //!     let admin_view = THIS_APP.config_file("admin_profiles")
 //!         .section("dev")
@@ -36,9 +36,50 @@
 //! }
 //! ```
 //!
+//! # Features
+//! - `tracing`: emits `tracing::debug!` events for config reads and writes, and for file and
+//!   directory creation. Only value lengths are recorded, never the values themselves.
+//! - `toml`, `json`: config files ending in `.toml` or `.json` are read and written in that
+//!   format instead of `ini`. Top-level tables map onto sections. `json` also adds
+//!   `Config::set_json_value` and `Config::get_json_value`, which store any `serde` type as
+//!   JSON in a single key.
+//! - `encrypt`: `Config::set_encrypted` and `Config::get_decrypted` store values encrypted
+//!   with AES-256-GCM under a passphrase.
+//! - `base64`: `Config::set_base64` and `Config::get_base64` store binary data.
+//! - `cache`: `Config::get_cached` keeps the parsed file in memory between reads.
+//! - `log`: `Config::get_log_level` and `Config::set_log_level` read and write `log::Level`s.
+//!   `Config::watch_and_reload` also logs reload errors through `log`.
+//! - `url`: `Config::get_url` and `Config::set_url` read and write validated `url::Url`s.
+//! - `uuid`: `Config::get_uuid`, `Config::set_uuid` and `Config::ensure_uuid`.
+//! - `xdg` (Unix only): `App::xdg_dirs` exposes the `xdg` crate's `BaseDirectories`.
+//! - `keyring`: `Config::get_secret` and `Config::set_secret` keep values in the platform
+//!   keychain instead of the config file.
+//! - `chrono`: `App::last_run_time` and `App::update_last_run_time`, stored as RFC 3339, and
+//!   `Config::get_date` and `Config::set_date` for `YYYY-MM-DD` dates.
+//! - `color`: colors the output of `Config::print_debug`.
+//!
 pub mod dirs;
 
 mod app;
+mod atomic;
+mod comments;
+mod debug;
 pub use app::{app, App};
 mod config;
+pub use config::{Config, WriteOrder};
+#[cfg(feature = "encrypt")]
+mod encrypt;
 mod error;
+mod format;
+pub use format::Format;
+mod macros;
+mod memory;
+pub use memory::InMemoryConfig;
+mod merged;
+pub use merged::MergedConfig;
+mod registry;
+pub use registry::{find_app, registered_apps};
+mod values;
+
+pub use error::{KettleError, Result};
+pub use ini::Ini;
@@ -0,0 +1,42 @@
+//! Macros for declaring config contents in source.
+
+/// Writes default values to an app's default config, keeping any values already set.
+///
+/// Entries go to the global section, or to the section given after the app. The whole set is
+/// applied in a single load-save cycle and the expression evaluates to `kettle::Result<()>`.
+///
+/// ## Example
+/// ```no_run
+/// const APP: kettle::App = kettle::app("app", None);
+///
+/// kettle::ini_defaults!(APP => {
+///     "theme" = "dark";
+///     /* seconds between autosaves */
+///     "autosave" = 30;
+/// })
+/// .unwrap();
+///
+/// kettle::ini_defaults!(APP, "window" => {
+///     "width" = 800;
+///     "height" = 600;
+/// })
+/// .unwrap();
+/// ```
+#[macro_export]
+macro_rules! ini_defaults {
+    ($app:expr => { $($key:literal = $value:expr;)* }) => {
+        $crate::ini_defaults!(@apply $app, None::<&str>, $($key = $value;)*)
+    };
+    ($app:expr, $section:literal => { $($key:literal = $value:expr;)* }) => {
+        $crate::ini_defaults!(@apply $app, Some($section), $($key = $value;)*)
+    };
+    (@apply $app:expr, $section:expr, $($key:literal = $value:expr;)*) => {
+        $app.config().apply(|config: &mut $crate::Ini| {
+            $(
+                if config.get_from($section, $key).is_none() {
+                    config.set_to($section, $key.to_string(), $value.to_string());
+                }
+            )*
+        })
+    };
+}
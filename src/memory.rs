@@ -0,0 +1,86 @@
+//! In-memory config that never touches disk.
+
+use crate::error::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+type Sections = HashMap<Option<String>, HashMap<String, String>>;
+type Store = Arc<Mutex<Sections>>;
+
+static STORES: OnceLock<Mutex<HashMap<&'static str, Store>>> = OnceLock::new();
+
+/// Ephemeral config with the same interface as `Config`, kept in memory instead of a file.
+///
+/// Handles for the same app share their state for the lifetime of the process.
+#[derive(Clone)]
+pub struct InMemoryConfig {
+    store: Store,
+    section: Option<&'static str>,
+}
+
+impl InMemoryConfig {
+    pub(crate) fn shared(name: &'static str) -> Self {
+        let stores = STORES.get_or_init(Default::default);
+        let store = stores
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(name)
+            .or_default()
+            .clone();
+        Self {
+            store,
+            section: None,
+        }
+    }
+
+    /// Gets value from config. Will return `None` if key does not exist.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let store = self.store.lock().unwrap_or_else(PoisonError::into_inner);
+        store
+            .get(&self.section.map(String::from))
+            .and_then(|section| section.get(key))
+            .cloned()
+    }
+    /// Gets value from config, or `default` if the key does not exist.
+    pub fn get_or(&self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+    /// Sets value to config. Keys with `None` values are removed.
+    pub fn set<S: Into<String>>(&self, key: &'static str, value: Option<S>) -> Result<()> {
+        let mut store = self.store.lock().unwrap_or_else(PoisonError::into_inner);
+        let section = store.entry(self.section.map(String::from)).or_default();
+        match value {
+            Some(v) => section.insert(key.to_string(), v.into()),
+            None => section.remove(key),
+        };
+        Ok(())
+    }
+
+    /// Adds a section to a config query.
+    pub fn section(mut self, section: &'static str) -> Self {
+        self.section = Some(section);
+        self
+    }
+}
+
+impl TryFrom<Sections> for InMemoryConfig {
+    type Error = KettleError;
+
+    /// Builds a standalone in-memory config from a section -> key -> value map. The result
+    /// does not share state with `App::runtime_config`.
+    fn try_from(sections: Sections) -> Result<Self> {
+        for (section, keys) in &sections {
+            if keys.keys().any(String::is_empty) {
+                return Err(KettleError::ValidationError(format!(
+                    "empty key name in section {:?}",
+                    section
+                )));
+            }
+        }
+        Ok(Self {
+            store: Arc::new(Mutex::new(sections)),
+            section: None,
+        })
+    }
+}
@@ -0,0 +1,65 @@
+//! Read-only config with environment variable overrides.
+
+use crate::config::Config;
+use ini::Ini;
+use std::borrow::Cow;
+use std::env;
+
+/// Environment variable name for `key`, following the `PREFIX_SECTION_KEY` convention.
+///
+/// Keys in the global section map to `PREFIX_KEY`.
+pub(crate) fn env_key(prefix: &str, section: Option<&str>, key: &str) -> String {
+    match section {
+        Some(section) => format!("{}_{}_{}", prefix, section, key),
+        None => format!("{}_{}", prefix, key),
+    }
+    .to_uppercase()
+}
+
+/// A snapshot of a config file where environment variables take precedence over file values.
+///
+/// Lookups check `PREFIX_SECTION_KEY` (or `PREFIX_KEY` in the global section) first.
+/// A `MergedConfig` never writes to disk.
+pub struct MergedConfig {
+    config: Ini,
+    prefix: String,
+    section: Option<Cow<'static, str>>,
+    sanitize: bool,
+}
+
+impl MergedConfig {
+    pub(crate) fn new(
+        config: Ini,
+        prefix: &str,
+        section: Option<Cow<'static, str>>,
+        sanitize: bool,
+    ) -> Self {
+        Self {
+            config,
+            prefix: prefix.to_string(),
+            section,
+            sanitize,
+        }
+    }
+
+    /// Gets value from the environment, falling back to the config file.
+    ///
+    /// Control characters are stripped from both if the `Config` was made with `sanitize`.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let section = self.section.as_deref();
+        let value = env::var(env_key(&self.prefix, section, key))
+            .ok()
+            .or_else(|| self.config.get_from(section, key).map(String::from))?;
+        if self.sanitize {
+            Some(Config::sanitize_value(&value))
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Adds a section to a config query.
+    pub fn section(mut self, section: &'static str) -> Self {
+        self.section = Some(Cow::Borrowed(section));
+        self
+    }
+}
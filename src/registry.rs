@@ -0,0 +1,30 @@
+//! Process-wide registry of known apps.
+
+use crate::app::App;
+use std::sync::{Mutex, PoisonError};
+
+static REGISTRY: Mutex<&'static [App]> = Mutex::new(&[]);
+
+/// Adds `app` to the registry unless an equal app is already registered.
+///
+/// Each new registration leaks a copy of the registry so that `registered_apps` can hand out a
+/// `'static` slice; this is bounded by the number of distinct apps in a binary.
+pub(crate) fn register(app: App) {
+    let mut apps = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    if apps.contains(&app) {
+        return;
+    }
+    let mut updated = apps.to_vec();
+    updated.push(app);
+    *apps = Box::leak(updated.into_boxed_slice());
+}
+
+/// All apps registered with `App::register`, in registration order.
+pub fn registered_apps() -> &'static [App] {
+    *REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The first registered app named `name`.
+pub fn find_app(name: &str) -> Option<&'static App> {
+    registered_apps().iter().find(|app| app.name() == name)
+}
@@ -0,0 +1,124 @@
+//! Parsing and formatting of typed config values.
+
+use std::time::Duration;
+
+const DURATION_UNITS: [(&str, u64); 4] = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+
+/// Parses durations such as `30s`, `5m`, `2h` or `1d`.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (unit, seconds) = DURATION_UNITS
+        .iter()
+        .find(|(unit, _)| value.ends_with(unit))?;
+    let amount: u64 = value[..value.len() - unit.len()].trim().parse().ok()?;
+    amount.checked_mul(*seconds).map(Duration::from_secs)
+}
+
+/// Formats a duration in the largest unit that divides it evenly, in whole seconds.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (unit, seconds) = DURATION_UNITS
+        .iter()
+        .find(|(_, seconds)| secs >= *seconds && secs.is_multiple_of(*seconds))
+        .unwrap_or(&("s", 1));
+    format!("{}{}", secs / seconds, unit)
+}
+
+/// Parses `true`/`false`, `1`/`0` and `yes`/`no`, ignoring case.
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses hex colors of the form `#RRGGBB`.
+pub(crate) fn parse_color(value: &str) -> Option<(u8, u8, u8)> {
+    let hex = value.trim().strip_prefix('#')?;
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Formats a color as `#RRGGBB`.
+pub(crate) fn format_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration(" 5m "), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2 h"), Some(Duration::from_secs(7_200)));
+        assert_eq!(parse_duration("1d"), Some(Duration::from_secs(86_400)));
+        assert_eq!(parse_duration("0s"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration("5ms"), None);
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_duration("-5s"), None);
+        assert_eq!(parse_duration("1.5h"), None);
+        assert_eq!(parse_duration("18446744073709551615d"), None);
+        assert_eq!(parse_duration("18446744073709551616s"), None);
+    }
+
+    #[test]
+    fn formats_durations_in_largest_unit() {
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_secs(120)), "2m");
+        assert_eq!(format_duration(Duration::from_secs(7_200)), "2h");
+        assert_eq!(format_duration(Duration::from_secs(172_800)), "2d");
+        assert_eq!(format_duration(Duration::from_millis(1_500)), "1s");
+        assert_eq!(format_duration(Duration::from_millis(5)), "0s");
+    }
+
+    #[test]
+    fn formatted_durations_parse_back() {
+        for secs in [0, 1, 59, 60, 3_599, 3_600, 86_400, 90_000] {
+            let duration = Duration::from_secs(secs);
+            assert_eq!(parse_duration(&format_duration(duration)), Some(duration));
+        }
+    }
+
+    #[test]
+    fn parses_bools() {
+        for value in ["true", "TRUE", "1", "yes", " Yes "] {
+            assert_eq!(parse_bool(value), Some(true), "{}", value);
+        }
+        for value in ["false", "False", "0", "no", "NO"] {
+            assert_eq!(parse_bool(value), Some(false), "{}", value);
+        }
+        for value in ["", "on", "off", "2", "y"] {
+            assert_eq!(parse_bool(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("#FF8000"), Some((255, 128, 0)));
+        assert_eq!(parse_color(" #0a0B0c "), Some((10, 11, 12)));
+        assert_eq!(parse_color("FF8000"), None);
+        assert_eq!(parse_color("#F80"), None);
+        assert_eq!(parse_color("#FF80001"), None);
+        assert_eq!(parse_color("#GG8000"), None);
+        assert_eq!(parse_color("#+F8000"), None);
+        assert_eq!(parse_color("red"), None);
+    }
+
+    #[test]
+    fn formatted_colors_parse_back() {
+        assert_eq!(format_color((255, 128, 0)), "#FF8000");
+        assert_eq!(parse_color(&format_color((1, 2, 3))), Some((1, 2, 3)));
+    }
+}